@@ -1,21 +1,118 @@
+use std::future::Future;
 use std::sync::Arc;
 
-use borsh::BorshSerialize;
-
-use solana_bridge::round_loader::RelayRoundProposalEventWithLen;
+use solana_bridge::bridge_types::Vote;
+use solana_bridge::round_loader::{
+    RelayRound, RelayRoundProposal, RelayRoundProposalEventWithLen, Settings,
+};
 use solana_client::rpc_client::RpcClient;
-use solana_client::tpu_client::{TpuClient, TpuClientConfig};
-use solana_program::bpf_loader_upgradeable;
-use solana_program::bpf_loader_upgradeable::UpgradeableLoaderState;
-use solana_program::message::Message;
+use solana_sdk::clock::Slot;
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::{Keypair, Signer};
-use solana_sdk::transaction::Transaction;
+use solana_sdk::signature::{Keypair, Signature, Signer};
 
-use crate::error::{Error, Result};
+use crate::error::Result;
+use crate::nonblocking;
+use crate::proposal::ProposalInput;
 use crate::utils;
 
+/// Outcome of a confirmed client operation.
+///
+/// Buffer and proposal writes list the signature each chunk landed with,
+/// chunks re-signed after their blockhash expired are listed once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Receipt {
+    /// Signatures of the transactions sent by the operation
+    pub signatures: Vec<Signature>,
+    /// Slot at which the operation was confirmed
+    pub slot: Slot,
+    /// Total fee paid, in lamports
+    pub fee: u64,
+    /// The transactions were only simulated, e.g. by a dry run
+    pub simulated: bool,
+}
+
+impl Receipt {
+    /// Accumulates the receipt of a subsequent operation.
+    pub fn merge(&mut self, other: Receipt) {
+        self.signatures.extend(other.signatures);
+        self.slot = self.slot.max(other.slot);
+        self.fee += other.fee;
+        self.simulated |= other.simulated;
+    }
+}
+
+/// Lamports required to deploy a program from scratch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeployCost {
+    /// Rent for the buffer, later moved to the ProgramData account
+    pub programdata_rent: u64,
+    /// Rent for the program account
+    pub program_rent: u64,
+    /// Number of buffer write transactions
+    pub write_chunks: usize,
+    /// Fees for the buffer write transactions
+    pub write_fees: u64,
+    /// Fees for the create buffer, deploy and set authority transactions
+    pub transaction_fees: u64,
+}
+
+impl DeployCost {
+    pub fn total(&self) -> u64 {
+        self.programdata_rent
+            .saturating_add(self.program_rent)
+            .saturating_add(self.write_fees)
+            .saturating_add(self.transaction_fees)
+    }
+}
+
+impl std::fmt::Display for DeployCost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "ProgramData rent: {} lamports", self.programdata_rent)?;
+        writeln!(f, "Program rent: {} lamports", self.program_rent)?;
+        writeln!(
+            f,
+            "Write fees: {} lamports ({} chunks)",
+            self.write_fees, self.write_chunks
+        )?;
+        writeln!(f, "Transaction fees: {} lamports", self.transaction_fees)?;
+        write!(f, "Total: {} lamports", self.total())
+    }
+}
+
+/// Accounts of a deployment, the messages its fees are estimated from depend
+/// on which of them sign.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeployKeys {
+    pub program: Pubkey,
+    pub buffer: Pubkey,
+    pub buffer_authority: Pubkey,
+    pub upgrade_authority: Pubkey,
+    /// `None` when the upgrade authority keeps the program
+    pub final_authority: Option<Pubkey>,
+}
+
+/// Upgradeable program as stored in its ProgramData account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramData {
+    /// Address of the ProgramData account
+    pub address: Pubkey,
+    /// Slot the program was last deployed at
+    pub slot: Slot,
+    /// `None` once the program is made immutable
+    pub upgrade_authority: Option<Pubkey>,
+    /// Program bytes, zero padded to the program capacity
+    pub data: Vec<u8>,
+}
+
+/// Program buffer account, as written by `write_buffer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferData {
+    pub authority: Option<Pubkey>,
+    /// Buffer bytes, zero padded to the buffer capacity
+    pub data: Vec<u8>,
+}
+
 /// Establishes a RPC connection with the solana cluster configured by
 /// `solana config set --url <URL>`. Information about what cluster
 /// has been configured is gleened from the solana config file
@@ -28,85 +125,71 @@ pub fn establish_connection() -> Result<Arc<RpcClient>> {
     )))
 }
 
+/// Runs a nonblocking client call on the runtime of `connection`. Like the
+/// blocking `RpcClient` methods, it may be called from within a multi-threaded
+/// tokio runtime.
+fn block_on<F: Future>(connection: &RpcClient, future: F) -> F::Output {
+    tokio::task::block_in_place(|| connection.runtime().block_on(future))
+}
+
+/// Computes the total cost of deploying `program_len` bytes into a program
+/// with `max_data_len` capacity and checks it against the payer balance,
+/// before any transaction is sent.
+pub fn check_deploy_cost(
+    payer: &Keypair,
+    keys: &DeployKeys,
+    program_len: usize,
+    max_data_len: usize,
+    connection: &Arc<RpcClient>,
+) -> Result<DeployCost> {
+    block_on(
+        connection,
+        nonblocking::client::check_deploy_cost(
+            payer,
+            keys,
+            program_len,
+            max_data_len,
+            connection.get_inner_client(),
+        ),
+    )
+}
+
 pub fn create_buffer(
     payer: &Keypair,
     buffer: &Keypair,
     authority_address: &Pubkey,
     program_len: usize,
     connection: &Arc<RpcClient>,
-) -> Result<()> {
-    utils::print_header("Creating buffer");
-
-    let minimum_balance = connection.get_minimum_balance_for_rent_exemption(
-        UpgradeableLoaderState::size_of_programdata(program_len),
-    )?;
-
-    let mut transaction = Transaction::new_with_payer(
-        &bpf_loader_upgradeable::create_buffer(
-            &payer.pubkey(),
-            &buffer.pubkey(),
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::create_buffer(
+            payer,
+            buffer,
             authority_address,
-            minimum_balance,
             program_len,
-        )?,
-        Some(&payer.pubkey()),
-    );
-    transaction.sign(&[payer, buffer], connection.get_latest_blockhash()?);
-
-    connection.send_and_confirm_transaction_with_spinner(&transaction)?;
-
-    println!("Buffer: {}", buffer.pubkey());
-
-    Ok(())
+            connection.get_inner_client(),
+        ),
+    )
 }
 
 pub fn write_buffer(
     payer: &Keypair,
+    buffer_authority: &Keypair,
     buffer_pubkey: &Pubkey,
     program_data: &[u8],
     connection: &Arc<RpcClient>,
-) -> Result<()> {
-    utils::print_header("Writing buffer");
-
-    let blockhash = connection.get_latest_blockhash()?;
-
-    // Get messages
-    let create_msg = |offset: u32, bytes: Vec<u8>| {
-        let instruction =
-            bpf_loader_upgradeable::write(buffer_pubkey, &payer.pubkey(), offset, bytes);
-        Message::new_with_blockhash(&[instruction], Some(&payer.pubkey()), &blockhash)
-    };
-
-    let mut write_messages = vec![];
-    let chunk_size = utils::calculate_max_chunk_size(&create_msg);
-    for (chunk, i) in program_data.chunks(chunk_size).zip(0..) {
-        write_messages.push(create_msg((i * chunk_size) as u32, chunk.to_vec()));
-    }
-
-    // Send message
-    let websocket_url = utils::get_ws_url()?;
-    let tpu_client = TpuClient::new(
-        connection.clone(),
-        &websocket_url,
-        TpuClientConfig::default(),
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::write_buffer(
+            payer,
+            buffer_authority,
+            buffer_pubkey,
+            program_data,
+            connection.get_inner_client(),
+        ),
     )
-    .map_err(Error::TpuSenderError)?;
-
-    let transaction_errors = tpu_client
-        .send_and_confirm_messages_with_spinner(&write_messages, &[payer])
-        .map_err(Error::TpuSenderError)?
-        .into_iter()
-        .flatten()
-        .collect::<Vec<_>>();
-
-    if !transaction_errors.is_empty() {
-        for transaction_error in &transaction_errors {
-            eprintln!("{:?}", transaction_error);
-        }
-        return Err(Error::WriteTransactions(transaction_errors.len()));
-    }
-
-    Ok(())
 }
 
 pub fn set_buffer_authority(
@@ -115,79 +198,110 @@ pub fn set_buffer_authority(
     buffer_address: &Pubkey,
     new_authority_address: &Pubkey,
     connection: &Arc<RpcClient>,
-) -> Result<()> {
-    utils::print_header("Setting buffer authority");
-
-    let mut transaction = Transaction::new_with_payer(
-        &[bpf_loader_upgradeable::set_buffer_authority(
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::set_buffer_authority(
+            payer,
+            current_authority,
             buffer_address,
-            &current_authority.pubkey(),
             new_authority_address,
-        )],
-        Some(&payer.pubkey()),
-    );
-    transaction.sign(&[payer], connection.get_latest_blockhash()?);
-
-    connection.send_and_confirm_transaction_with_spinner(&transaction)?;
-
-    println!("Authority: {}", new_authority_address);
-
-    Ok(())
+            connection.get_inner_client(),
+        ),
+    )
 }
 
 pub fn deploy(
     payer: &Keypair,
     program: &Keypair,
     buffer_pubkey: &Pubkey,
+    upgrade_authority: &dyn Signer,
     max_data_len: usize,
     connection: &Arc<RpcClient>,
-) -> Result<()> {
-    utils::print_header("Deploying program");
-
-    let mut transaction = Transaction::new_with_payer(
-        &bpf_loader_upgradeable::deploy_with_max_program_len(
-            &payer.pubkey(),
-            &program.pubkey(),
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::deploy(
+            payer,
+            program,
             buffer_pubkey,
-            &payer.pubkey(),
-            connection
-                .get_minimum_balance_for_rent_exemption(UpgradeableLoaderState::size_of_program())?,
+            upgrade_authority,
             max_data_len,
-        )?,
-        Some(&payer.pubkey()),
-    );
-    transaction.sign(&[payer, program], connection.get_latest_blockhash()?);
-
-    connection.send_and_confirm_transaction_with_spinner(&transaction)?;
-
-    println!("Program: {}", program.pubkey());
-
-    Ok(())
+            connection.get_inner_client(),
+        ),
+    )
 }
 
 pub fn set_program_authority(
-    current_authority: &Keypair,
+    payer: &Keypair,
+    current_authority: &dyn Signer,
     program_address: &Pubkey,
     new_authority_address: &Pubkey,
     connection: &Arc<RpcClient>,
-) -> Result<()> {
-    utils::print_header("Setting program authority");
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::set_program_authority(
+            payer,
+            current_authority,
+            program_address,
+            new_authority_address,
+            connection.get_inner_client(),
+        ),
+    )
+}
 
-    let mut transaction = Transaction::new_with_payer(
-        &[bpf_loader_upgradeable::set_upgrade_authority(
+pub fn upgrade(
+    payer: &Keypair,
+    authority: &Keypair,
+    program_address: &Pubkey,
+    buffer_address: &Pubkey,
+    connection: &Arc<RpcClient>,
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::upgrade(
+            payer,
+            authority,
             program_address,
-            &current_authority.pubkey(),
-            Some(new_authority_address),
-        )],
-        Some(&current_authority.pubkey()),
-    );
-    transaction.sign(&[current_authority], connection.get_latest_blockhash()?);
+            buffer_address,
+            connection.get_inner_client(),
+        ),
+    )
+}
 
-    connection.send_and_confirm_transaction_with_spinner(&transaction)?;
+pub fn extend_program(
+    payer: &Keypair,
+    program_address: &Pubkey,
+    additional_bytes: u32,
+    connection: &Arc<RpcClient>,
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::extend_program(
+            payer,
+            program_address,
+            additional_bytes,
+            connection.get_inner_client(),
+        ),
+    )
+}
 
-    println!("Authority: {}", new_authority_address);
+pub fn get_program_data(
+    program_address: &Pubkey,
+    connection: &Arc<RpcClient>,
+) -> Result<Option<ProgramData>> {
+    block_on(
+        connection,
+        nonblocking::client::get_program_data(program_address, connection.get_inner_client()),
+    )
+}
 
-    Ok(())
+pub fn get_buffer(buffer_address: &Pubkey, connection: &Arc<RpcClient>) -> Result<BufferData> {
+    block_on(
+        connection,
+        nonblocking::client::get_buffer(buffer_address, connection.get_inner_client()),
+    )
 }
 
 pub fn create_relay_round_proposal(
@@ -198,26 +312,19 @@ pub fn create_relay_round_proposal(
     event_configuration: Pubkey,
     proposal: &RelayRoundProposalEventWithLen,
     connection: &Arc<RpcClient>,
-) -> Result<()> {
-    utils::print_header("Create Relay Round Proposal");
-
-    let mut transaction = Transaction::new_with_payer(
-        &[solana_bridge::round_loader::create_proposal_ix(
-            &payer.pubkey(),
-            &payer.pubkey(),
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::create_relay_round_proposal(
+            payer,
             round_number,
             event_timestamp,
             event_transaction_lt,
             event_configuration,
-            &proposal.data.try_to_vec()?,
-        )],
-        Some(&payer.pubkey()),
-    );
-    transaction.sign(&[payer], connection.get_latest_blockhash()?);
-
-    connection.send_and_confirm_transaction_with_spinner(&transaction)?;
-
-    Ok(())
+            proposal,
+            connection.get_inner_client(),
+        ),
+    )
 }
 
 pub fn write_relay_round_proposal(
@@ -225,71 +332,122 @@ pub fn write_relay_round_proposal(
     proposal_pubkey: &Pubkey,
     proposal: &RelayRoundProposalEventWithLen,
     connection: &Arc<RpcClient>,
-) -> Result<()> {
-    utils::print_header("Writing Relay Round Proposal");
-
-    let blockhash = connection.get_latest_blockhash()?;
-
-    let create_msg = |offset: u32, bytes: Vec<u8>| {
-        let instruction = solana_bridge::round_loader::write_proposal_ix(
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::write_relay_round_proposal(
+            payer,
             proposal_pubkey,
-            offset,
-            bytes,
-        );
-        Message::new_with_blockhash(&[instruction], Some(&payer.pubkey()), &blockhash)
-    };
-
-    let mut write_messages = vec![];
-    let chunk_size = utils::calculate_max_chunk_size(&create_msg);
-    for (chunk, i) in proposal.try_to_vec()?.chunks(chunk_size).zip(0..) {
-        write_messages.push(create_msg((i * chunk_size) as u32, chunk.to_vec()));
-    }
+            proposal,
+            connection.get_inner_client(),
+        ),
+    )
+}
 
-    // Send message
-    let websocket_url = utils::get_ws_url()?;
-    let tpu_client = TpuClient::new(
-        connection.clone(),
-        &websocket_url,
-        TpuClientConfig::default(),
+pub fn finalize_relay_round_proposal(
+    payer: &Keypair,
+    proposal_pubkey: &Pubkey,
+    round_number: u32,
+    connection: &Arc<RpcClient>,
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::finalize_relay_round_proposal(
+            payer,
+            proposal_pubkey,
+            round_number,
+            connection.get_inner_client(),
+        ),
     )
-    .map_err(Error::TpuSenderError)?;
-
-    let transaction_errors = tpu_client
-        .send_and_confirm_messages_with_spinner(&write_messages, &[payer])
-        .map_err(Error::TpuSenderError)?
-        .into_iter()
-        .flatten()
-        .collect::<Vec<_>>();
-
-    if !transaction_errors.is_empty() {
-        for transaction_error in &transaction_errors {
-            eprintln!("{:?}", transaction_error);
-        }
-        return Err(Error::WriteTransactions(transaction_errors.len()));
-    }
+}
 
-    Ok(())
+pub fn vote_for_relay_round_proposal(
+    voter: &Keypair,
+    proposal_pubkey: &Pubkey,
+    vote: Vote,
+    connection: &Arc<RpcClient>,
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::vote_for_relay_round_proposal(
+            voter,
+            proposal_pubkey,
+            vote,
+            connection.get_inner_client(),
+        ),
+    )
 }
 
-pub fn finalize_relay_round_proposal(
+pub fn execute_relay_round_proposal(
     payer: &Keypair,
     proposal_pubkey: &Pubkey,
+    connection: &Arc<RpcClient>,
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::execute_relay_round_proposal(
+            payer,
+            proposal_pubkey,
+            connection.get_inner_client(),
+        ),
+    )
+}
+
+pub fn propose_relay_round(
+    payer: &Keypair,
     round_number: u32,
+    input: &ProposalInput,
+    connection: &Arc<RpcClient>,
+) -> Result<Receipt> {
+    block_on(
+        connection,
+        nonblocking::client::propose_relay_round(
+            payer,
+            round_number,
+            input,
+            connection.get_inner_client(),
+        ),
+    )
+}
+
+pub fn watch_relay_round_proposal(
+    proposal_pubkey: &Pubkey,
+    websocket_url: &str,
     connection: &Arc<RpcClient>,
 ) -> Result<()> {
-    utils::print_header("Finalize Relay Round Proposal");
+    block_on(
+        connection,
+        nonblocking::client::watch_relay_round_proposal(
+            proposal_pubkey,
+            websocket_url,
+            connection.get_inner_client(),
+        ),
+    )
+}
 
-    let mut transaction = Transaction::new_with_payer(
-        &[solana_bridge::round_loader::finalize_proposal_ix(
-            &payer.pubkey(),
+pub fn get_relay_round_proposal(
+    proposal_pubkey: &Pubkey,
+    connection: &Arc<RpcClient>,
+) -> Result<RelayRoundProposal> {
+    block_on(
+        connection,
+        nonblocking::client::get_relay_round_proposal(
             proposal_pubkey,
-            round_number,
-        )],
-        Some(&payer.pubkey()),
-    );
-    transaction.sign(&[payer], connection.get_latest_blockhash()?);
+            connection.get_inner_client(),
+        ),
+    )
+}
 
-    connection.send_and_confirm_transaction_with_spinner(&transaction)?;
+pub fn get_round_loader_settings(connection: &Arc<RpcClient>) -> Result<Settings> {
+    block_on(
+        connection,
+        nonblocking::client::get_round_loader_settings(connection.get_inner_client()),
+    )
+}
 
-    Ok(())
+pub fn get_relay_round(round_number: u32, connection: &Arc<RpcClient>) -> Result<RelayRound> {
+    block_on(
+        connection,
+        nonblocking::client::get_relay_round(round_number, connection.get_inner_client()),
+    )
 }
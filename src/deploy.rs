@@ -0,0 +1,451 @@
+use solana_program::bpf_loader_upgradeable;
+use solana_sdk::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::client::{DeployCost, DeployKeys, Receipt};
+use crate::error::{InputError, OnChainError, Result};
+use crate::journal::{Journal, JournalPlan, Stage};
+use crate::nonblocking::client::{
+    check_deploy_cost, create_buffer, deploy, get_buffer, get_program_data, set_buffer_authority,
+    set_program_authority, write_buffer,
+};
+use crate::rpc::Rpc;
+
+/// Step of a deployment that has landed on chain.
+#[derive(Debug)]
+pub enum Checkpoint<'a> {
+    /// Deployment cost fits the payer balance
+    CostChecked(&'a DeployCost),
+    /// Buffer account is created, with the buffer authority as its authority
+    BufferCreated {
+        buffer: Pubkey,
+        receipt: &'a Receipt,
+    },
+    /// Program bytes are written to the buffer
+    BufferWritten {
+        buffer: Pubkey,
+        receipt: &'a Receipt,
+    },
+    /// Buffer is handed over to the upgrade authority
+    BufferAuthoritySet {
+        buffer: Pubkey,
+        authority: Pubkey,
+        receipt: &'a Receipt,
+    },
+    /// Program is deployed from the buffer, with the upgrade authority
+    ProgramDeployed {
+        program: Pubkey,
+        receipt: &'a Receipt,
+    },
+    /// Upgrade authority is handed over to the final authority
+    AuthoritySet {
+        program: Pubkey,
+        authority: Pubkey,
+        receipt: &'a Receipt,
+    },
+}
+
+/// Outcome of a completed deployment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeployReceipt {
+    pub program_id: Pubkey,
+    pub program_data_address: Pubkey,
+    pub buffer: Pubkey,
+    pub upgrade_authority: Pubkey,
+    pub max_data_len: usize,
+    /// `None` for a resumed deployment
+    pub cost: Option<DeployCost>,
+    /// Merged receipt of every transaction sent by the deployment
+    pub receipt: Receipt,
+}
+
+/// What to deploy and who pays and signs for it.
+///
+/// The payer only pays fees and rent. Unless a buffer or upgrade authority is
+/// set it also signs for them, and holds the program until the handoff.
+///
+/// When the upgrade authority is the final authority, e.g. a Ledger, the
+/// buffer is handed over to it and the deploy instruction names it directly,
+/// so no other key ever controls the program. Otherwise the program is handed
+/// over once deployed, and the handoff is read back from the chain.
+///
+/// ```ignore
+/// let receipt = DeployPlan::new(&program_data, &payer, &program)
+///     .buffer_authority(&deployer)
+///     .upgrade_authority(&upgrader)
+///     .max_data_len(program_data.len() * 2)
+///     .final_authority(multisig)
+///     .deployer(&connection)
+///     .on_checkpoint(|checkpoint| println!("{:?}", checkpoint))
+///     .run()
+///     .await?;
+/// ```
+pub struct DeployPlan<'a> {
+    program_data: &'a [u8],
+    payer: &'a Keypair,
+    program: &'a Keypair,
+    buffer: Option<&'a Keypair>,
+    buffer_authority: Option<&'a Keypair>,
+    upgrade_authority: Option<&'a dyn Signer>,
+    max_data_len: Option<usize>,
+    final_authority: Option<Pubkey>,
+    journal: Option<Journal>,
+}
+
+impl<'a> DeployPlan<'a> {
+    pub fn new(program_data: &'a [u8], payer: &'a Keypair, program: &'a Keypair) -> Self {
+        Self {
+            program_data,
+            payer,
+            program,
+            buffer: None,
+            buffer_authority: None,
+            upgrade_authority: None,
+            max_data_len: None,
+            final_authority: None,
+            journal: None,
+        }
+    }
+
+    /// Buffer account keypair, a new one is generated by default.
+    pub fn buffer(mut self, buffer: &'a Keypair) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// Signs the buffer writes, defaults to the payer.
+    pub fn buffer_authority(mut self, authority: &'a Keypair) -> Self {
+        self.buffer_authority = Some(authority);
+        self
+    }
+
+    /// Signs the deployment and is the upgrade authority of the deployed
+    /// program, defaults to the buffer authority. The buffer is handed over
+    /// to it once written.
+    pub fn upgrade_authority(mut self, authority: &'a dyn Signer) -> Self {
+        self.upgrade_authority = Some(authority);
+        self
+    }
+
+    /// Program capacity in bytes, defaults to the program length.
+    pub fn max_data_len(mut self, max_data_len: usize) -> Self {
+        self.max_data_len = Some(max_data_len);
+        self
+    }
+
+    /// Upgrade authority once deployed, the upgrade authority stays the
+    /// authority by default.
+    pub fn final_authority(mut self, authority: Pubkey) -> Self {
+        self.final_authority = Some(authority);
+        self
+    }
+
+    /// Records every landed step to `journal`. A journal loaded from an
+    /// interrupted deployment resumes it.
+    pub fn journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Binds the plan to the transport its transactions are sent through.
+    pub fn deployer<R: Rpc + ?Sized>(self, connection: &'a R) -> Deployer<'a, R> {
+        Deployer {
+            plan: self,
+            connection,
+            on_checkpoint: Box::new(|_| {}),
+        }
+    }
+}
+
+/// Runs a [`DeployPlan`] step by step: cost check, buffer creation, buffer
+/// write, buffer handoff, deployment and authority handoff.
+pub struct Deployer<'a, R: ?Sized> {
+    plan: DeployPlan<'a>,
+    connection: &'a R,
+    on_checkpoint: Box<dyn FnMut(Checkpoint) + Send + 'a>,
+}
+
+impl<'a, R: Rpc + ?Sized> Deployer<'a, R> {
+    /// Called after every step that lands.
+    pub fn on_checkpoint<F>(mut self, on_checkpoint: F) -> Self
+    where
+        F: FnMut(Checkpoint) + Send + 'a,
+    {
+        self.on_checkpoint = Box::new(on_checkpoint);
+        self
+    }
+
+    pub async fn run(mut self) -> Result<DeployReceipt> {
+        let DeployPlan {
+            program_data,
+            payer,
+            program,
+            buffer,
+            buffer_authority,
+            upgrade_authority,
+            max_data_len,
+            final_authority,
+            mut journal,
+        } = self.plan;
+        let connection = self.connection;
+
+        let buffer_authority = buffer_authority.unwrap_or(payer);
+        let upgrade_authority = upgrade_authority.unwrap_or(buffer_authority);
+
+        let max_data_len = max_data_len.unwrap_or(program_data.len());
+        let generated_buffer;
+        let buffer = match buffer {
+            Some(buffer) => buffer,
+            None => {
+                generated_buffer = Keypair::new();
+                &generated_buffer
+            }
+        };
+
+        // A resumed deployment skips the steps found on chain. Journaled steps
+        // are not trusted on their own, e.g. a dry run only simulated them
+        let mut completed = None;
+        let mut new_plan = None;
+        if let Some(journal) = &mut journal {
+            let plan = JournalPlan {
+                program_sha256: hex::encode(hash(program_data).to_bytes()),
+                payer: payer.pubkey(),
+                program: program.pubkey(),
+                buffer: buffer.pubkey(),
+                buffer_authority: buffer_authority.pubkey(),
+                upgrade_authority: upgrade_authority.pubkey(),
+                max_data_len,
+                final_authority,
+            };
+
+            match journal.plan() {
+                None => new_plan = Some(plan),
+                Some(started) if *started == plan => {
+                    completed = landed_stage(&plan, program_data, connection).await?;
+                }
+                Some(_) => {
+                    return Err(InputError::InvalidJournal(
+                        "deployment does not match the journal".to_string(),
+                    )
+                    .into())
+                }
+            }
+        }
+        let done = |stage| completed >= Some(stage);
+
+        let cost = match completed {
+            None => {
+                let keys = DeployKeys {
+                    program: program.pubkey(),
+                    buffer: buffer.pubkey(),
+                    buffer_authority: buffer_authority.pubkey(),
+                    upgrade_authority: upgrade_authority.pubkey(),
+                    final_authority,
+                };
+                let cost =
+                    check_deploy_cost(payer, &keys, program_data.len(), max_data_len, connection)
+                        .await?;
+                (self.on_checkpoint)(Checkpoint::CostChecked(&cost));
+                Some(cost)
+            }
+            Some(_) => None,
+        };
+
+        // Started once the cost check passed, a run aborted before anything
+        // was sent leaves no journal behind
+        if let (Some(journal), Some(plan)) = (&mut journal, new_plan) {
+            journal.start(plan, program, buffer)?;
+        }
+
+        let mut receipt = Receipt::default();
+
+        if !done(Stage::BufferCreated) {
+            let step = create_buffer(
+                payer,
+                buffer,
+                &buffer_authority.pubkey(),
+                max_data_len,
+                connection,
+            )
+            .await?;
+            record(&mut journal, Stage::BufferCreated, &step)?;
+            (self.on_checkpoint)(Checkpoint::BufferCreated {
+                buffer: buffer.pubkey(),
+                receipt: &step,
+            });
+            receipt.merge(step);
+        }
+
+        if !done(Stage::BufferWritten) {
+            let step = write_buffer(
+                payer,
+                buffer_authority,
+                &buffer.pubkey(),
+                program_data,
+                connection,
+            )
+            .await?;
+            record(&mut journal, Stage::BufferWritten, &step)?;
+            (self.on_checkpoint)(Checkpoint::BufferWritten {
+                buffer: buffer.pubkey(),
+                receipt: &step,
+            });
+            receipt.merge(step);
+        }
+
+        // The loader only deploys a buffer signed for by its authority
+        if buffer_authority.pubkey() != upgrade_authority.pubkey()
+            && !done(Stage::BufferAuthoritySet)
+        {
+            let step = set_buffer_authority(
+                payer,
+                buffer_authority,
+                &buffer.pubkey(),
+                &upgrade_authority.pubkey(),
+                connection,
+            )
+            .await?;
+            record(&mut journal, Stage::BufferAuthoritySet, &step)?;
+            (self.on_checkpoint)(Checkpoint::BufferAuthoritySet {
+                buffer: buffer.pubkey(),
+                authority: upgrade_authority.pubkey(),
+                receipt: &step,
+            });
+            receipt.merge(step);
+        }
+
+        if !done(Stage::ProgramDeployed) {
+            let step = deploy(
+                payer,
+                program,
+                &buffer.pubkey(),
+                upgrade_authority,
+                max_data_len,
+                connection,
+            )
+            .await?;
+            record(&mut journal, Stage::ProgramDeployed, &step)?;
+            (self.on_checkpoint)(Checkpoint::ProgramDeployed {
+                program: program.pubkey(),
+                receipt: &step,
+            });
+            receipt.merge(step);
+        }
+
+        let upgrade_authority = match final_authority {
+            Some(authority) if authority != upgrade_authority.pubkey() => {
+                if !done(Stage::AuthoritySet) {
+                    let step = set_program_authority(
+                        payer,
+                        upgrade_authority,
+                        &program.pubkey(),
+                        &authority,
+                        connection,
+                    )
+                    .await?;
+                    // Nothing landed to read back from a simulated handoff
+                    if !step.simulated {
+                        confirm_upgrade_authority(&program.pubkey(), &authority, connection)
+                            .await?;
+                    }
+                    record(&mut journal, Stage::AuthoritySet, &step)?;
+                    (self.on_checkpoint)(Checkpoint::AuthoritySet {
+                        program: program.pubkey(),
+                        authority,
+                        receipt: &step,
+                    });
+                    receipt.merge(step);
+                }
+                authority
+            }
+            _ => upgrade_authority.pubkey(),
+        };
+
+        // Only a completed deployment, authority handoff included, drops its journal
+        if let Some(journal) = journal {
+            journal.finish()?;
+        }
+
+        let (program_data_address, _) = Pubkey::find_program_address(
+            &[program.pubkey().as_ref()],
+            &bpf_loader_upgradeable::id(),
+        );
+
+        Ok(DeployReceipt {
+            program_id: program.pubkey(),
+            program_data_address,
+            buffer: buffer.pubkey(),
+            upgrade_authority,
+            max_data_len,
+            cost,
+            receipt,
+        })
+    }
+}
+
+/// Reads the upgrade authority back, the program stays with the previous
+/// authority if the handoff did not land.
+async fn confirm_upgrade_authority<R: Rpc + ?Sized>(
+    program: &Pubkey,
+    expected: &Pubkey,
+    connection: &R,
+) -> Result<()> {
+    let program_data = get_program_data(program, connection)
+        .await?
+        .ok_or(OnChainError::AccountNotFound(*program))?;
+    match program_data.upgrade_authority {
+        Some(actual) if actual == *expected => Ok(()),
+        Some(actual) => Err(OnChainError::UnexpectedAuthority {
+            address: *program,
+            expected: *expected,
+            actual,
+        }
+        .into()),
+        None => Err(OnChainError::ImmutableProgram(*program).into()),
+    }
+}
+
+fn record(journal: &mut Option<Journal>, stage: Stage, receipt: &Receipt) -> Result<()> {
+    match journal {
+        Some(journal) => journal.record(stage, receipt),
+        None => Ok(()),
+    }
+}
+
+/// Last step found on chain. A step lands without being journaled when the
+/// deployment stops while waiting for its confirmation, and a journaled step
+/// may never have landed.
+async fn landed_stage<R: Rpc + ?Sized>(
+    plan: &JournalPlan,
+    program_data: &[u8],
+    connection: &R,
+) -> Result<Option<Stage>> {
+    if let Some(deployed) = get_program_data(&plan.program, connection).await? {
+        let handed_over = match plan.final_authority {
+            Some(authority) => deployed.upgrade_authority == Some(authority),
+            None => true,
+        };
+        return Ok(Some(if handed_over {
+            Stage::AuthoritySet
+        } else {
+            Stage::ProgramDeployed
+        }));
+    }
+
+    if connection.get_account(&plan.buffer).await?.is_some() {
+        let buffer = get_buffer(&plan.buffer, connection).await?;
+        let handed_over = plan.buffer_authority != plan.upgrade_authority
+            && buffer.authority == Some(plan.upgrade_authority);
+        let written = buffer.data.get(..program_data.len()) == Some(program_data);
+        return Ok(Some(if handed_over {
+            Stage::BufferAuthoritySet
+        } else if written {
+            Stage::BufferWritten
+        } else {
+            Stage::BufferCreated
+        }));
+    }
+
+    Ok(None)
+}
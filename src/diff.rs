@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::elf::{self, ElfSection};
+use crate::error::Result;
+
+/// Strings shorter than this are mostly noise in `.rodata`.
+const MIN_STRING_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+impl Change {
+    fn of(deployed: Option<&[u8]>, candidate: Option<&[u8]>) -> Self {
+        match (deployed, candidate) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Removed,
+            (Some(deployed), Some(candidate)) if deployed == candidate => Change::Unchanged,
+            _ => Change::Changed,
+        }
+    }
+}
+
+/// Section or symbol of either build, with its size in each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryDiff {
+    pub name: String,
+    pub deployed_size: Option<usize>,
+    pub candidate_size: Option<usize>,
+    pub change: Change,
+}
+
+/// Differences between a deployed program and a candidate build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramDiff {
+    pub deployed_len: usize,
+    pub candidate_len: usize,
+    /// Bytes available in the ProgramData account
+    pub capacity: usize,
+    /// Every section of both builds
+    pub sections: Vec<EntryDiff>,
+    /// Symbols that differ between the builds
+    pub symbols: Vec<EntryDiff>,
+    /// Strings only found in the candidate `.rodata`
+    pub rodata_added: Vec<String>,
+    /// Strings only found in the deployed `.rodata`
+    pub rodata_removed: Vec<String>,
+}
+
+impl ProgramDiff {
+    /// Compares two ELFs, `deployed` may carry the zero padding of its
+    /// ProgramData account.
+    pub fn new(deployed: &[u8], candidate: &[u8], capacity: usize) -> Result<Self> {
+        let deployed_sections = elf::parse_sections(deployed)?;
+        let deployed = &deployed[..elf::elf_len(deployed, &deployed_sections)?.min(deployed.len())];
+        let candidate_sections = elf::parse_sections(candidate)?;
+
+        let sections = diff(
+            section_data(deployed, &deployed_sections),
+            section_data(candidate, &candidate_sections),
+        );
+
+        let symbol_data =
+            |elf: &[u8], sections: &[ElfSection]| -> Result<BTreeMap<String, Vec<u8>>> {
+                Ok(elf::parse_symbols(elf, sections)?
+                    .into_iter()
+                    .map(|symbol| {
+                        let data = symbol.data(elf, sections).to_vec();
+                        (symbol.name, data)
+                    })
+                    .collect())
+            };
+        let symbols = diff(
+            symbol_data(deployed, &deployed_sections)?,
+            symbol_data(candidate, &candidate_sections)?,
+        )
+        .into_iter()
+        .filter(|symbol| symbol.change != Change::Unchanged)
+        .collect();
+
+        let rodata = |elf: &[u8], sections: &[ElfSection]| {
+            sections
+                .iter()
+                .find(|section| section.name == ".rodata")
+                .map(|section| strings(section.data(elf)))
+                .unwrap_or_default()
+        };
+        let deployed_rodata = rodata(deployed, &deployed_sections);
+        let candidate_rodata = rodata(candidate, &candidate_sections);
+
+        Ok(Self {
+            deployed_len: deployed.len(),
+            candidate_len: candidate.len(),
+            capacity,
+            sections,
+            symbols,
+            rodata_added: candidate_rodata
+                .difference(&deployed_rodata)
+                .cloned()
+                .collect(),
+            rodata_removed: deployed_rodata
+                .difference(&candidate_rodata)
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// Whether the candidate can be deployed without extending the program.
+    pub fn fits(&self) -> bool {
+        self.candidate_len <= self.capacity
+    }
+}
+
+fn section_data(elf: &[u8], sections: &[ElfSection]) -> BTreeMap<String, Vec<u8>> {
+    sections
+        .iter()
+        .filter(|section| !section.name.is_empty())
+        .map(|section| (section.name.clone(), section.data(elf).to_vec()))
+        .collect()
+}
+
+fn diff(
+    deployed: BTreeMap<String, Vec<u8>>,
+    candidate: BTreeMap<String, Vec<u8>>,
+) -> Vec<EntryDiff> {
+    let names = deployed
+        .keys()
+        .chain(candidate.keys())
+        .collect::<BTreeSet<_>>();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let deployed = deployed.get(name).map(Vec::as_slice);
+            let candidate = candidate.get(name).map(Vec::as_slice);
+            EntryDiff {
+                name: name.clone(),
+                deployed_size: deployed.map(<[u8]>::len),
+                candidate_size: candidate.map(<[u8]>::len),
+                change: Change::of(deployed, candidate),
+            }
+        })
+        .collect()
+}
+
+/// Printable ASCII runs, as `strings` would list them.
+fn strings(data: &[u8]) -> BTreeSet<String> {
+    data.split(|byte| !(byte.is_ascii_graphic() || *byte == b' '))
+        .filter(|run| run.len() >= MIN_STRING_LEN)
+        .map(|run| String::from_utf8_lossy(run).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf::fixture::{self, SHT_PROGBITS, SHT_STRTAB, SHT_SYMTAB};
+
+    /// `.text`, `.rodata` and a symbol table, `symbols` are `(name, offset
+    /// in .text, size)`.
+    fn elf(text: &[u8], rodata: &[u8], symbols: &[(&str, u64, u64)]) -> Vec<u8> {
+        // `.text` is the first section, loaded right after the header
+        let symbols = symbols
+            .iter()
+            .map(|(name, offset, size)| (*name, 1, 64 + offset, *size))
+            .collect::<Vec<_>>();
+        let (symtab, strtab) = fixture::symbols(&symbols);
+        fixture::elf(&[
+            (".text", SHT_PROGBITS, text.to_vec()),
+            (".rodata", SHT_PROGBITS, rodata.to_vec()),
+            (".symtab", SHT_SYMTAB, symtab),
+            (".strtab", SHT_STRTAB, strtab),
+        ])
+    }
+
+    fn deployed() -> Vec<u8> {
+        elf(
+            &[1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3],
+            b"hello world\0shared message\0",
+            &[("entrypoint", 0, 4), ("helper", 4, 4), ("legacy", 8, 4)],
+        )
+    }
+
+    fn candidate() -> Vec<u8> {
+        elf(
+            &[1, 1, 1, 1, 9, 9, 9, 9, 4, 4, 4, 4, 4, 4, 4, 4],
+            b"shared message\0new message\0",
+            &[("entrypoint", 0, 4), ("helper", 4, 4), ("added", 8, 8)],
+        )
+    }
+
+    fn entry<'a>(entries: &'a [EntryDiff], name: &str) -> &'a EntryDiff {
+        entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .unwrap_or_else(|| panic!("missing entry `{}`", name))
+    }
+
+    #[test]
+    fn sections() {
+        let diff = ProgramDiff::new(&deployed(), &candidate(), 4096).unwrap();
+
+        let text = entry(&diff.sections, ".text");
+        assert_eq!(text.change, Change::Changed);
+        assert_eq!(text.deployed_size, Some(12));
+        assert_eq!(text.candidate_size, Some(16));
+        assert_eq!(entry(&diff.sections, ".rodata").change, Change::Changed);
+        assert_eq!(entry(&diff.sections, ".shstrtab").change, Change::Unchanged);
+        assert_eq!(diff.sections.len(), 5);
+    }
+
+    #[test]
+    fn symbols() {
+        let diff = ProgramDiff::new(&deployed(), &candidate(), 4096).unwrap();
+
+        assert!(diff
+            .symbols
+            .iter()
+            .all(|symbol| symbol.name != "entrypoint"));
+        assert_eq!(entry(&diff.symbols, "helper").change, Change::Changed);
+        assert_eq!(entry(&diff.symbols, "legacy").change, Change::Removed);
+        let added = entry(&diff.symbols, "added");
+        assert_eq!(added.change, Change::Added);
+        assert_eq!(added.deployed_size, None);
+        assert_eq!(added.candidate_size, Some(8));
+        assert_eq!(diff.symbols.len(), 3);
+    }
+
+    #[test]
+    fn rodata_strings() {
+        let diff = ProgramDiff::new(&deployed(), &candidate(), 4096).unwrap();
+
+        assert_eq!(diff.rodata_added, vec!["new message".to_string()]);
+        assert_eq!(diff.rodata_removed, vec!["hello world".to_string()]);
+    }
+
+    #[test]
+    fn capacity() {
+        let deployed = deployed();
+        let candidate = candidate();
+
+        // The deployed ELF is read back with the zero padding of its account
+        let mut padded = deployed.clone();
+        padded.resize(candidate.len() - 1, 0);
+        let capacity = padded.len();
+
+        let diff = ProgramDiff::new(&padded, &candidate, capacity).unwrap();
+        assert_eq!(diff.deployed_len, deployed.len());
+        assert_eq!(diff.candidate_len, candidate.len());
+        assert!(!diff.fits());
+
+        let diff = ProgramDiff::new(&padded, &candidate, candidate.len()).unwrap();
+        assert!(diff.fits());
+        assert!(diff
+            .sections
+            .iter()
+            .all(|section| section.change != Change::Added));
+    }
+}
@@ -0,0 +1,473 @@
+use std::sync::Arc;
+
+use solana_bpf_loader_program::syscalls::create_program_runtime_environment;
+use solana_program::bpf_loader_upgradeable::UpgradeableLoaderState;
+use solana_program::system_instruction::MAX_PERMITTED_DATA_LENGTH;
+use solana_program_runtime::compute_budget::ComputeBudget;
+use solana_program_runtime::invoke_context::InvokeContext;
+use solana_program_runtime::solana_rbpf::elf::Executable;
+use solana_program_runtime::solana_rbpf::verifier::{RequisiteVerifier, TautologyVerifier};
+use solana_sdk::feature_set::FeatureSet;
+
+use crate::error::{InputError, Result};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_DYN: u16 = 3;
+const EM_BPF: u16 = 247;
+const EM_SBF: u16 = 263;
+const SHT_NOBITS: u32 = 8;
+const ELF64_HEADER_LEN: usize = 64;
+const ELF64_SECTION_HEADER_LEN: usize = 64;
+const ELF64_SYMBOL_LEN: usize = 24;
+
+/// Symbol the loader starts the program at.
+const ENTRYPOINT: &str = "entrypoint";
+
+/// Section of a program ELF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfSection {
+    pub name: String,
+    pub kind: u32,
+    /// Virtual address the section is loaded at
+    pub address: u64,
+    pub offset: usize,
+    pub size: usize,
+}
+
+impl ElfSection {
+    /// Bytes of the section, empty for sections without file data.
+    pub fn data<'a>(&self, elf: &'a [u8]) -> &'a [u8] {
+        if self.kind == SHT_NOBITS {
+            return &[];
+        }
+        &elf[self.offset..self.offset + self.size]
+    }
+}
+
+/// Checks that `program_data` is a deployable SBF program, so a wrong file is
+/// rejected before any rent is paid: header, sections, entrypoint and size
+/// limits, then the same verifier the loader runs on deployment.
+pub fn validate_elf(program_data: &[u8]) -> Result<Vec<ElfSection>> {
+    let max_len =
+        MAX_PERMITTED_DATA_LENGTH as usize - UpgradeableLoaderState::size_of_programdata_metadata();
+    if program_data.len() > max_len {
+        return Err(invalid(format!(
+            "program is ({}) bytes, at most ({}) fit a program account",
+            program_data.len(),
+            max_len
+        )));
+    }
+
+    let sections = parse_sections(program_data)?;
+
+    if !sections.iter().any(|section| section.name == ".text") {
+        return Err(invalid("missing `.text` section".to_string()));
+    }
+    if let Some(section) = sections
+        .iter()
+        .find(|section| section.name.starts_with(".debug_"))
+    {
+        return Err(invalid(format!(
+            "found `{}` section, deploy the stripped build from `target/deploy`",
+            section.name
+        )));
+    }
+    if !has_symbol(program_data, &sections, ENTRYPOINT)? {
+        return Err(invalid(format!("missing `{}` symbol", ENTRYPOINT)));
+    }
+
+    verify(program_data)?;
+
+    Ok(sections)
+}
+
+/// Parses the ELF header and section table.
+pub fn parse_sections(elf: &[u8]) -> Result<Vec<ElfSection>> {
+    if elf.len() < ELF64_HEADER_LEN || &elf[..4] != ELF_MAGIC {
+        return Err(invalid("not an ELF file".to_string()));
+    }
+    if elf[4] != ELFCLASS64 || elf[5] != ELFDATA2LSB {
+        return Err(invalid("expected a 64-bit little endian ELF".to_string()));
+    }
+    if read_u16(elf, 16)? != ET_DYN {
+        return Err(invalid("expected a shared object".to_string()));
+    }
+    match read_u16(elf, 18)? {
+        EM_BPF | EM_SBF => {}
+        machine => {
+            return Err(invalid(format!(
+                "machine ({}) is neither SBF nor BPF, is this a host build?",
+                machine
+            )))
+        }
+    }
+
+    let section_headers_offset = read_u64(elf, 40)? as usize;
+    let section_header_len = read_u16(elf, 58)? as usize;
+    let sections_count = read_u16(elf, 60)? as usize;
+    let names_index = read_u16(elf, 62)? as usize;
+    if section_header_len != ELF64_SECTION_HEADER_LEN
+        || names_index >= sections_count
+        || section_headers_offset
+            .checked_add(sections_count * ELF64_SECTION_HEADER_LEN)
+            .is_none_or(|end| end > elf.len())
+    {
+        return Err(invalid("malformed section header table".to_string()));
+    }
+
+    let headers = (0..sections_count)
+        .map(|i| {
+            let header = section_headers_offset + i * ELF64_SECTION_HEADER_LEN;
+            let kind = read_u32(elf, header + 4)?;
+            let address = read_u64(elf, header + 16)?;
+            let offset = read_u64(elf, header + 24)? as usize;
+            let size = read_u64(elf, header + 32)? as usize;
+            if kind != SHT_NOBITS && offset.checked_add(size).is_none_or(|end| end > elf.len()) {
+                return Err(invalid(format!(
+                    "section #{} is out of bounds, the file may be truncated",
+                    i
+                )));
+            }
+            Ok((read_u32(elf, header)? as usize, kind, address, offset, size))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (_, names_kind, _, names_offset, names_size) = headers[names_index];
+    if names_kind == SHT_NOBITS {
+        return Err(invalid("malformed section name table".to_string()));
+    }
+    let names = &elf[names_offset..names_offset + names_size];
+
+    headers
+        .into_iter()
+        .map(|(name, kind, address, offset, size)| {
+            Ok(ElfSection {
+                name: read_str(names, name)?,
+                kind,
+                address,
+                offset,
+                size,
+            })
+        })
+        .collect()
+}
+
+/// Symbol of a program ELF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfSymbol {
+    pub name: String,
+    /// Index of the section the symbol is defined in
+    pub section: usize,
+    pub address: u64,
+    pub size: usize,
+}
+
+impl ElfSymbol {
+    /// Bytes the symbol spans, empty when they are not stored in the file.
+    pub fn data<'a>(&self, elf: &'a [u8], sections: &[ElfSection]) -> &'a [u8] {
+        let section = match sections.get(self.section) {
+            Some(section) => section,
+            None => return &[],
+        };
+        let start = match self.address.checked_sub(section.address) {
+            Some(start) => start as usize,
+            None => return &[],
+        };
+        section
+            .data(elf)
+            .get(start..start.saturating_add(self.size))
+            .unwrap_or_default()
+    }
+}
+
+/// Named symbols from `.symtab`, or from `.dynsym` for stripped programs.
+pub fn parse_symbols(elf: &[u8], sections: &[ElfSection]) -> Result<Vec<ElfSymbol>> {
+    let find = |name: &str| sections.iter().find(|section| section.name == name);
+    let (symbols, names) = match (find(".symtab"), find(".strtab")) {
+        (Some(symbols), Some(names)) => (symbols, names),
+        _ => match (find(".dynsym"), find(".dynstr")) {
+            (Some(symbols), Some(names)) => (symbols, names),
+            _ => return Ok(Vec::new()),
+        },
+    };
+    let names = names.data(elf);
+
+    symbols
+        .data(elf)
+        .chunks_exact(ELF64_SYMBOL_LEN)
+        .map(|symbol| {
+            Ok(ElfSymbol {
+                name: read_str(names, read_u32(symbol, 0)? as usize)?,
+                section: read_u16(symbol, 6)? as usize,
+                address: read_u64(symbol, 8)?,
+                size: read_u64(symbol, 16)? as usize,
+            })
+        })
+        .filter(|symbol| !matches!(symbol, Ok(symbol) if symbol.name.is_empty()))
+        .collect()
+}
+
+/// Length of the ELF at the start of `data`, e.g. of a program stored in a
+/// zero padded ProgramData account.
+pub fn elf_len(data: &[u8], sections: &[ElfSection]) -> Result<usize> {
+    let section_headers_end =
+        read_u64(data, 40)? as usize + read_u16(data, 60)? as usize * ELF64_SECTION_HEADER_LEN;
+
+    Ok(sections
+        .iter()
+        .filter(|section| section.kind != SHT_NOBITS)
+        .map(|section| section.offset + section.size)
+        .fold(section_headers_end, usize::max))
+}
+
+fn has_symbol(elf: &[u8], sections: &[ElfSection], name: &str) -> Result<bool> {
+    let (symbols, names) = match (
+        sections.iter().find(|section| section.name == ".dynsym"),
+        sections.iter().find(|section| section.name == ".dynstr"),
+    ) {
+        (Some(symbols), Some(names)) => (symbols.data(elf), names.data(elf)),
+        _ => return Ok(false),
+    };
+
+    for symbol in symbols.chunks_exact(ELF64_SYMBOL_LEN) {
+        if read_str(names, read_u32(symbol, 0)? as usize)? == name {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Runs the loader's verifier with every feature enabled.
+fn verify(program_data: &[u8]) -> Result<()> {
+    let runtime_environment = create_program_runtime_environment(
+        &FeatureSet::all_enabled(),
+        &ComputeBudget::default(),
+        true,
+        false,
+    )
+    .map_err(|e| invalid(e.to_string()))?;
+
+    let executable = Executable::<TautologyVerifier, InvokeContext>::from_elf(
+        program_data,
+        Arc::new(runtime_environment),
+    )
+    .map_err(|e| invalid(e.to_string()))?;
+    Executable::<RequisiteVerifier, InvokeContext>::verified(executable)
+        .map_err(|e| invalid(format!("verifier rejected the program: {}", e)))?;
+
+    Ok(())
+}
+
+fn invalid(reason: String) -> crate::error::Error {
+    InputError::InvalidElf(reason).into()
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    offset
+        .checked_add(N)
+        .and_then(|end| data.get(offset..end))
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| invalid("unexpected end of file".to_string()))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    read_bytes(data, offset).map(u64::from_le_bytes)
+}
+
+fn read_str(names: &[u8], offset: usize) -> Result<String> {
+    let name = names
+        .get(offset..)
+        .and_then(|name| name.split(|byte| *byte == 0).next())
+        .ok_or_else(|| invalid("invalid string table offset".to_string()))?;
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Hand-built ELF files for unit tests.
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::*;
+
+    pub const SHT_PROGBITS: u32 = 1;
+    pub const SHT_SYMTAB: u32 = 2;
+    pub const SHT_STRTAB: u32 = 3;
+    pub const SHT_DYNSYM: u32 = 11;
+
+    /// SBF shared object holding `sections`, each loaded at its file offset,
+    /// followed by `.shstrtab`. The first section starts right after the
+    /// header, at offset 64.
+    pub fn elf(sections: &[(&str, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut shstrtab = vec![0];
+        let mut name_offsets = Vec::new();
+        for name in sections
+            .iter()
+            .map(|(name, _, _)| *name)
+            .chain([".shstrtab"])
+        {
+            name_offsets.push(shstrtab.len() as u32);
+            shstrtab.extend_from_slice(name.as_bytes());
+            shstrtab.push(0);
+        }
+
+        let mut elf = vec![0; ELF64_HEADER_LEN];
+        let mut headers = vec![[0u8; ELF64_SECTION_HEADER_LEN]];
+        let contents = sections
+            .iter()
+            .map(|(_, kind, data)| (*kind, data.as_slice()))
+            .chain([(SHT_STRTAB, shstrtab.as_slice())]);
+        for ((kind, data), name) in contents.zip(name_offsets) {
+            let offset = elf.len() as u64;
+            let mut header = [0u8; ELF64_SECTION_HEADER_LEN];
+            header[0..4].copy_from_slice(&name.to_le_bytes());
+            header[4..8].copy_from_slice(&kind.to_le_bytes());
+            header[16..24].copy_from_slice(&offset.to_le_bytes());
+            header[24..32].copy_from_slice(&offset.to_le_bytes());
+            header[32..40].copy_from_slice(&(data.len() as u64).to_le_bytes());
+            headers.push(header);
+            elf.extend_from_slice(data);
+        }
+
+        let section_headers_offset = elf.len() as u64;
+        for header in &headers {
+            elf.extend_from_slice(header);
+        }
+
+        elf[..4].copy_from_slice(ELF_MAGIC);
+        elf[4] = ELFCLASS64;
+        elf[5] = ELFDATA2LSB;
+        elf[6] = 1;
+        elf[16..18].copy_from_slice(&ET_DYN.to_le_bytes());
+        elf[18..20].copy_from_slice(&EM_SBF.to_le_bytes());
+        elf[40..48].copy_from_slice(&section_headers_offset.to_le_bytes());
+        elf[52..54].copy_from_slice(&(ELF64_HEADER_LEN as u16).to_le_bytes());
+        elf[58..60].copy_from_slice(&(ELF64_SECTION_HEADER_LEN as u16).to_le_bytes());
+        elf[60..62].copy_from_slice(&(headers.len() as u16).to_le_bytes());
+        elf[62..64].copy_from_slice(&(sections.len() as u16 + 1).to_le_bytes());
+        elf
+    }
+
+    /// Symbol and string tables, `symbols` are `(name, section index,
+    /// address, size)`.
+    pub fn symbols(symbols: &[(&str, u16, u64, u64)]) -> (Vec<u8>, Vec<u8>) {
+        let mut table = vec![0; ELF64_SYMBOL_LEN];
+        let mut names = vec![0];
+        for (name, section, address, size) in symbols {
+            table.extend_from_slice(&(names.len() as u32).to_le_bytes());
+            table.extend_from_slice(&[0x12, 0]);
+            table.extend_from_slice(&section.to_le_bytes());
+            table.extend_from_slice(&address.to_le_bytes());
+            table.extend_from_slice(&size.to_le_bytes());
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+        (table, names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixture::*;
+    use super::*;
+    use crate::error::Error;
+
+    /// `.text` with a dynamic symbol table exporting `symbol`.
+    fn program(symbol: &str) -> Vec<(&'static str, u32, Vec<u8>)> {
+        let (dynsym, dynstr) = symbols(&[(symbol, 1, 64, 8)]);
+        vec![
+            (".text", SHT_PROGBITS, vec![0x95, 0, 0, 0, 0, 0, 0, 0]),
+            (".dynsym", SHT_DYNSYM, dynsym),
+            (".dynstr", SHT_STRTAB, dynstr),
+        ]
+    }
+
+    fn assert_invalid(program_data: &[u8], expected: &str) {
+        match validate_elf(program_data) {
+            Err(Error::Input(InputError::InvalidElf(reason))) => assert!(
+                reason.contains(expected),
+                "`{}` does not mention `{}`",
+                reason,
+                expected
+            ),
+            result => panic!("expected an invalid ELF, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn reject_bad_magic() {
+        let mut program_data = elf(&program(ENTRYPOINT));
+        program_data[..4].copy_from_slice(b"\x7fEXE");
+        assert_invalid(&program_data, "not an ELF file");
+    }
+
+    #[test]
+    fn reject_32_bit_class() {
+        let mut program_data = elf(&program(ENTRYPOINT));
+        program_data[4] = 1;
+        assert_invalid(&program_data, "64-bit little endian");
+    }
+
+    #[test]
+    fn reject_host_machine() {
+        let mut program_data = elf(&program(ENTRYPOINT));
+        // EM_X86_64
+        program_data[18..20].copy_from_slice(&62u16.to_le_bytes());
+        assert_invalid(&program_data, "host build");
+    }
+
+    #[test]
+    fn reject_truncated_section_table() {
+        let program_data = elf(&program(ENTRYPOINT));
+        assert_invalid(
+            &program_data[..program_data.len() - 1],
+            "malformed section header table",
+        );
+    }
+
+    #[test]
+    fn reject_truncated_section() {
+        let mut program_data = elf(&program(ENTRYPOINT));
+        // Section #1 size, pushing `.text` past the end of the file
+        let section_headers_offset = read_u64(&program_data, 40).unwrap() as usize;
+        let size = section_headers_offset + ELF64_SECTION_HEADER_LEN + 32;
+        program_data[size..size + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_invalid(&program_data, "section #1 is out of bounds");
+    }
+
+    #[test]
+    fn reject_debug_sections() {
+        let mut sections = program(ENTRYPOINT);
+        sections.push((".debug_info", SHT_PROGBITS, vec![1, 2, 3]));
+        assert_invalid(&elf(&sections), "found `.debug_info` section");
+    }
+
+    #[test]
+    fn reject_missing_text() {
+        let (dynsym, dynstr) = symbols(&[(ENTRYPOINT, 1, 64, 8)]);
+        let program_data = elf(&[
+            (".dynsym", SHT_DYNSYM, dynsym),
+            (".dynstr", SHT_STRTAB, dynstr),
+        ]);
+        assert_invalid(&program_data, "missing `.text` section");
+    }
+
+    #[test]
+    fn reject_missing_entrypoint() {
+        assert_invalid(&elf(&program("process")), "missing `entrypoint` symbol");
+    }
+
+    #[test]
+    fn reject_oversized_program() {
+        let program_data = vec![0; MAX_PERMITTED_DATA_LENGTH as usize];
+        assert_invalid(&program_data, "at most");
+    }
+}
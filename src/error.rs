@@ -1,51 +1,269 @@
+use solana_client::client_error::ClientError;
+use solana_program::program_error::ProgramError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
 use thiserror::Error;
 
+/// Errors grouped by what the operator has to do about them. Every category
+/// exits the CLI with its own status code, see [`Error::exit_code`].
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("failed to read solana config file: ({0})")]
-    ConfigReadError(std::io::Error),
+    #[error("config error: {0}")]
+    Config(#[from] ConfigError),
+    #[error("invalid input: {0}")]
+    Input(#[from] InputError),
+    #[error("signer error: {0}")]
+    Signer(#[from] SignerError),
+    #[error("network error: {0}")]
+    Network(#[from] NetworkError),
+    #[error("on-chain error: {0}")]
+    OnChain(#[from] OnChainError),
+    #[error("partial write: {0}")]
+    PartialWrite(#[from] PartialWriteError),
+}
+
+impl Error {
+    /// Process exit code for scripting, following `sysexits.h`.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_CONFIG
+            Error::Config(_) => 78,
+            // EX_DATAERR
+            Error::Input(_) => 65,
+            // EX_NOPERM
+            Error::Signer(_) => 77,
+            // EX_UNAVAILABLE
+            Error::Network(_) => 69,
+            // EX_PROTOCOL
+            Error::OnChain(_) => 76,
+            // EX_TEMPFAIL, rerunning the operation completes the write
+            Error::PartialWrite(_) => 75,
+        }
+    }
+}
+
+/// Solana CLI config file problems.
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("failed to locate home directory")]
+    HomeDirNotFound,
+    #[error("failed to read solana config file ({path}): ({source})")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
     #[error("failed to parse solana config file: ({0})")]
-    ConfigParseError(#[from] yaml_rust::ScanError),
+    Parse(#[from] yaml_rust::ScanError),
     #[error("invalid config: ({0})")]
-    InvalidConfig(String),
-    #[error("invalid pubkey")]
-    InvalidPubkey,
-    #[error("invalid program path")]
-    InvalidProgramPath,
-    #[error("failed to open program file: ({0})")]
-    ProgramOpenError(std::io::Error),
-    #[error("failed to read program file: ({0})")]
-    ProgramReadError(std::io::Error),
-    #[error("failed to read keypair file")]
-    KeypairReadError,
-    #[error("failed to write keypair file")]
-    KeypairWriteError,
-    #[error("invalid event timestamp")]
-    InvalidEventTimestamp,
-    #[error("invalid transaction lt")]
-    InvalidTransactionLt,
-    #[error("invalid configuration")]
-    InvalidConfiguration,
-    #[error("invalid round number")]
-    InvalidRoundNumber,
-    #[error("invalid proposal round number")]
-    InvalidProposalRoundNumber,
-    #[error("invalid proposal relays")]
-    InvalidProposalRelays,
-    #[error("({0}) write transactions failed")]
-    WriteTransactions(usize),
+    Invalid(String),
+}
 
-    #[error("solana client error: ({0})")]
-    ClientError(#[from] solana_client::client_error::ClientError),
+/// Invalid command line arguments or input files.
+#[derive(Error, Debug)]
+pub enum InputError {
+    #[error("invalid `{0}` argument")]
+    InvalidArgument(&'static str),
+    #[error("invalid pubkey: ({0})")]
+    InvalidPubkey(String),
+    #[error("invalid everscale address: ({0})")]
+    InvalidEverscaleAddress(String),
+    #[error("expected an address in workchain 0, got workchain ({0})")]
+    InvalidWorkchain(i8),
+    #[error("failed to read program file ({path}): ({source})")]
+    ProgramRead {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid program ELF: ({0})")]
+    InvalidElf(String),
+    #[error("program is ({program_len}) bytes, `--program-size` only allows ({max_data_len})")]
+    ProgramSizeTooSmall {
+        program_len: usize,
+        max_data_len: usize,
+    },
+    #[error("failed to read proposal file ({path}): ({source})")]
+    ProposalFileRead {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid proposal: ({0})")]
+    InvalidProposal(String),
+    #[error("failed to read manifest ({path}): ({source})")]
+    ManifestRead {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid manifest: ({0})")]
+    InvalidManifest(String),
+    #[error("failed to read lockfile ({path}): ({source})")]
+    LockfileRead {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("failed to write lockfile ({path}): ({source})")]
+    LockfileWrite {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid lockfile: ({0})")]
+    InvalidLockfile(String),
+    #[error("unfinished deployment journal ({0}) exists, run `resume` to complete it")]
+    JournalExists(String),
+    #[error("failed to read journal ({path}): ({source})")]
+    JournalRead {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("failed to write journal ({path}): ({source})")]
+    JournalWrite {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid journal: ({0})")]
+    InvalidJournal(String),
+    #[error("failed to write file ({path}): ({source})")]
+    FileWrite {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error(
+        "keypair file ({0}) already exists, pass `--program-keypair-out` to write the generated keypair elsewhere"
+    )]
+    KeypairOutputExists(String),
+    #[error("invalid vanity prefix: ({0})")]
+    InvalidVanityPrefix(String),
+    #[error("failed to build instruction: ({0})")]
+    Instruction(#[from] solana_program::instruction::InstructionError),
+    #[error("failed to encode data: ({0})")]
+    Encoding(#[from] std::io::Error),
+}
+
+/// Keypairs that can not be loaded or stored.
+#[derive(Error, Debug)]
+pub enum SignerError {
+    #[error("failed to read keypair file ({path}): ({reason})")]
+    KeypairRead { path: String, reason: String },
+    #[error("failed to write keypair file ({path}): ({reason})")]
+    KeypairWrite { path: String, reason: String },
+    #[error("keypair file ({0}) already exists, refusing to overwrite it")]
+    KeypairExists(String),
+    #[error("failed to load signer ({path}): ({reason})")]
+    SignerRead { path: String, reason: String },
+    #[error("failed to sign transaction: ({0})")]
+    Sign(String),
+}
 
+/// Failures talking to the cluster, where the transaction may not have landed.
+#[derive(Error, Debug)]
+pub enum NetworkError {
+    #[error("solana client error: ({0})")]
+    Client(ClientError),
+    #[error("banks client error: ({0})")]
+    BanksClient(solana_banks_client::BanksClientError),
+    #[error("pubsub client error: ({0})")]
+    PubsubClient(#[from] solana_client::nonblocking::pubsub_client::PubsubClientError),
     #[error("tpu sender error: ({0})")]
-    TpuSenderError(#[from] solana_client::tpu_client::TpuSenderError),
+    TpuSender(#[from] solana_client::tpu_client::TpuSenderError),
+    #[error("subscription to account ({0}) closed")]
+    SubscriptionClosed(Pubkey),
+}
 
-    #[error("solana instruction error: ({0})")]
-    InstructionError(#[from] solana_program::instruction::InstructionError),
+/// Failed transactions and on-chain state that does not allow an operation.
+#[derive(Error, Debug)]
+pub enum OnChainError {
+    #[error("transaction failed: ({0})")]
+    Transaction(TransactionError),
+    #[error("instruction #{index} of program ({program_id}) failed: {reason}")]
+    ProgramFailed {
+        index: u8,
+        program_id: Pubkey,
+        reason: String,
+    },
+    #[error("account ({0}) not found")]
+    AccountNotFound(Pubkey),
+    #[error("account ({0}) is owned by ({1})")]
+    InvalidAccountOwner(Pubkey, Pubkey),
+    #[error("failed to decode account ({0}): ({1})")]
+    AccountDecode(Pubkey, ProgramError),
+    #[error(
+        "insufficient funds: payer has ({balance}) lamports, deployment requires ({required})"
+    )]
+    InsufficientFunds { balance: u64, required: u64 },
+    #[error("({0}) is not a relay in round ({1})")]
+    NotARelay(Pubkey, u32),
+    #[error("not enough votes: ({confirmed}) confirmed, ({required}) required")]
+    NotEnoughVotes { confirmed: u32, required: u32 },
+    #[error("invalid proposal state: ({0})")]
+    InvalidProposalState(String),
+    #[error("program ({0}) is immutable")]
+    ImmutableProgram(Pubkey),
+    #[error("account ({address}) authority is ({actual}), expected ({expected})")]
+    UnexpectedAuthority {
+        address: Pubkey,
+        expected: Pubkey,
+        actual: Pubkey,
+    },
+    #[error("({0}) programs do not match the lockfile")]
+    LockfileMismatch(usize),
+    #[error("relay round ({1}) ended before proposal ({0}) was executed")]
+    ProposalExpired(Pubkey, u32),
+}
+
+/// Some of a batch of write transactions failed, leaving the account
+/// partially written.
+#[derive(Error, Debug)]
+#[error("({}) of ({total}) write transactions failed", .errors.len())]
+pub struct PartialWriteError {
+    pub total: usize,
+    pub errors: Vec<TransactionError>,
+}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        match e.get_transaction_error() {
+            Some(transaction_error) => OnChainError::Transaction(transaction_error).into(),
+            None => NetworkError::Client(e).into(),
+        }
+    }
+}
+
+impl From<solana_banks_client::BanksClientError> for Error {
+    fn from(e: solana_banks_client::BanksClientError) -> Self {
+        use solana_banks_client::BanksClientError;
+
+        match e {
+            BanksClientError::TransactionError(transaction_error)
+            | BanksClientError::SimulationError {
+                err: transaction_error,
+                ..
+            } => OnChainError::Transaction(transaction_error).into(),
+            e => NetworkError::BanksClient(e).into(),
+        }
+    }
+}
+
+impl From<solana_client::nonblocking::pubsub_client::PubsubClientError> for Error {
+    fn from(e: solana_client::nonblocking::pubsub_client::PubsubClientError) -> Self {
+        NetworkError::from(e).into()
+    }
+}
+
+impl From<solana_client::tpu_client::TpuSenderError> for Error {
+    fn from(e: solana_client::tpu_client::TpuSenderError) -> Self {
+        NetworkError::from(e).into()
+    }
+}
+
+impl From<solana_program::instruction::InstructionError> for Error {
+    fn from(e: solana_program::instruction::InstructionError) -> Self {
+        InputError::from(e).into()
+    }
+}
 
-    #[error("std error: ({0})")]
-    StdIoError(#[from] std::io::Error),
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        InputError::from(e).into()
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
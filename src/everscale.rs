@@ -0,0 +1,179 @@
+use std::str::FromStr;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::error::{Error, InputError, Result};
+
+/// Everscale standard address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EverAddress {
+    pub workchain: i8,
+    pub address: [u8; 32],
+}
+
+impl EverAddress {
+    /// Returns the account id as a pubkey, accepting only basechain addresses
+    /// so that a configuration from another network is caught early.
+    pub fn to_pubkey(&self) -> Result<Pubkey> {
+        if self.workchain != 0 {
+            return Err(InputError::InvalidWorkchain(self.workchain).into());
+        }
+        Ok(Pubkey::new_from_array(self.address))
+    }
+}
+
+impl FromStr for EverAddress {
+    type Err = Error;
+
+    /// Parses raw `<workchain>:<hex>` addresses, bare hex account ids, which
+    /// are assumed to be in the basechain, and base64 or base64url
+    /// user-friendly addresses.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        if let Some((workchain, hex)) = s.split_once(':') {
+            let workchain = workchain
+                .parse()
+                .map_err(|_| InputError::InvalidEverscaleAddress(s.to_string()))?;
+            return Ok(Self {
+                workchain,
+                address: decode_hex(hex)
+                    .ok_or_else(|| InputError::InvalidEverscaleAddress(s.to_string()))?,
+            });
+        }
+
+        match s.len() {
+            64 => Ok(Self {
+                workchain: 0,
+                address: decode_hex(s)
+                    .ok_or_else(|| InputError::InvalidEverscaleAddress(s.to_string()))?,
+            }),
+            48 => decode_user_friendly(s)
+                .ok_or_else(|| InputError::InvalidEverscaleAddress(s.to_string()).into()),
+            _ => Err(InputError::InvalidEverscaleAddress(s.to_string()).into()),
+        }
+    }
+}
+
+fn decode_hex(s: &str) -> Option<[u8; 32]> {
+    hex::decode(s).ok()?.try_into().ok()
+}
+
+/// User-friendly address layout: flags (1 byte), workchain (1 byte),
+/// account id (32 bytes) and CRC16 of the preceding bytes (2 bytes).
+fn decode_user_friendly(s: &str) -> Option<EverAddress> {
+    let s = s.replace('-', "+").replace('_', "/");
+    let bytes = base64::decode(s).ok()?;
+    if bytes.len() != 36 {
+        return None;
+    }
+
+    let crc = u16::from_be_bytes([bytes[34], bytes[35]]);
+    if crc16(&bytes[..34]) != crc {
+        return None;
+    }
+
+    Some(EverAddress {
+        workchain: bytes[1] as i8,
+        address: bytes[2..34].try_into().ok()?,
+    })
+}
+
+/// CRC-16/XMODEM
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT_ID: &str = "4dbd7a4bb09b0b4b44e3a2e2c5c3a0f6a1e1c3f0d5e6a7b8c9d0e1f2a3b4c5d6";
+
+    fn user_friendly(workchain: i8, address: [u8; 32]) -> Vec<u8> {
+        let mut bytes = vec![0x11, workchain as u8];
+        bytes.extend_from_slice(&address);
+        let crc = crc16(&bytes);
+        bytes.extend_from_slice(&crc.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn parse_raw() {
+        let address = EverAddress::from_str(&format!("0:{}", ACCOUNT_ID)).unwrap();
+        assert_eq!(address.workchain, 0);
+        assert_eq!(address.address.to_vec(), hex::decode(ACCOUNT_ID).unwrap());
+    }
+
+    #[test]
+    fn parse_hex() {
+        let address = EverAddress::from_str(ACCOUNT_ID).unwrap();
+        assert_eq!(address.workchain, 0);
+        assert_eq!(address.address.to_vec(), hex::decode(ACCOUNT_ID).unwrap());
+        assert_eq!(
+            address.to_pubkey().unwrap(),
+            Pubkey::new_from_array(address.address)
+        );
+    }
+
+    #[test]
+    fn parse_base64url() {
+        let account_id = decode_hex(ACCOUNT_ID).unwrap();
+        let encoded = base64::encode_config(user_friendly(0, account_id), base64::URL_SAFE);
+        assert_eq!(encoded.len(), 48);
+
+        let address = EverAddress::from_str(&encoded).unwrap();
+        assert_eq!(
+            address,
+            EverAddress {
+                workchain: 0,
+                address: account_id,
+            }
+        );
+    }
+
+    #[test]
+    fn reject_bad_checksum() {
+        let mut bytes = user_friendly(0, decode_hex(ACCOUNT_ID).unwrap());
+        bytes[35] ^= 1;
+        let encoded = base64::encode_config(bytes, base64::URL_SAFE);
+
+        assert!(matches!(
+            EverAddress::from_str(&encoded),
+            Err(Error::Input(InputError::InvalidEverscaleAddress(_)))
+        ));
+    }
+
+    #[test]
+    fn reject_non_zero_workchain() {
+        let raw = EverAddress::from_str(&format!("-1:{}", ACCOUNT_ID)).unwrap();
+        assert_eq!(raw.workchain, -1);
+        assert!(matches!(
+            raw.to_pubkey(),
+            Err(Error::Input(InputError::InvalidWorkchain(-1)))
+        ));
+
+        let encoded = base64::encode_config(
+            user_friendly(-1, decode_hex(ACCOUNT_ID).unwrap()),
+            base64::URL_SAFE,
+        );
+        let user_friendly = EverAddress::from_str(&encoded).unwrap();
+        assert_eq!(user_friendly, raw);
+    }
+
+    #[test]
+    fn crc16_xmodem_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+    }
+}
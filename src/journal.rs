@@ -0,0 +1,335 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use yaml_rust::yaml::Hash as YamlHash;
+use yaml_rust::{Yaml, YamlEmitter};
+
+use solana_sdk::clock::Slot;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+
+use crate::client::Receipt;
+use crate::error::{InputError, Result, SignerError};
+use crate::utils;
+
+/// Deployment step, in the order they are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    BufferCreated,
+    BufferWritten,
+    BufferAuthoritySet,
+    ProgramDeployed,
+    AuthoritySet,
+}
+
+impl Stage {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Stage::BufferCreated => "buffer_created",
+            Stage::BufferWritten => "buffer_written",
+            Stage::BufferAuthoritySet => "buffer_authority_set",
+            Stage::ProgramDeployed => "program_deployed",
+            Stage::AuthoritySet => "authority_set",
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = InputError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "buffer_created" => Ok(Stage::BufferCreated),
+            "buffer_written" => Ok(Stage::BufferWritten),
+            "buffer_authority_set" => Ok(Stage::BufferAuthoritySet),
+            "program_deployed" => Ok(Stage::ProgramDeployed),
+            "authority_set" => Ok(Stage::AuthoritySet),
+            _ => Err(InputError::InvalidJournal(format!("unknown stage `{}`", s))),
+        }
+    }
+}
+
+/// What a journaled deployment was started with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalPlan {
+    /// Hex encoded SHA-256 of the program
+    pub program_sha256: String,
+    pub payer: Pubkey,
+    pub program: Pubkey,
+    pub buffer: Pubkey,
+    pub buffer_authority: Pubkey,
+    pub upgrade_authority: Pubkey,
+    pub max_data_len: usize,
+    pub final_authority: Option<Pubkey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalStep {
+    pub stage: Stage,
+    pub signatures: Vec<Signature>,
+    pub slot: Slot,
+}
+
+/// Local record of a deployment in progress, so that a crashed deployment
+/// can be resumed instead of leaving an intermediate upgrade authority.
+///
+/// The file holds the program and buffer keypairs and is only readable by
+/// its owner. It is removed once the deployment completes.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    plan: Option<JournalPlan>,
+    program_keypair: String,
+    buffer_keypair: String,
+    steps: Vec<JournalStep>,
+    read_only: bool,
+}
+
+impl Journal {
+    /// Journal for a new deployment, written when the deployment starts.
+    pub fn new(path: PathBuf) -> Result<Self> {
+        if path.exists() {
+            return Err(InputError::JournalExists(path.display().to_string()).into());
+        }
+
+        Ok(Self {
+            path,
+            plan: None,
+            program_keypair: String::new(),
+            buffer_keypair: String::new(),
+            steps: Vec::new(),
+            read_only: false,
+        })
+    }
+
+    /// Journal of an interrupted deployment.
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let content = std::fs::read_to_string(&path).map_err(|source| InputError::JournalRead {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let document = utils::load_yaml(&content).map_err(InputError::InvalidJournal)?;
+
+        let invalid = |name: &str| InputError::InvalidJournal(format!("invalid `{}`", name));
+        let field = |name: &str| document[name].as_str().ok_or_else(|| invalid(name));
+        let pubkey = |name: &str| {
+            field(name).and_then(|value| Pubkey::from_str(value).map_err(|_| invalid(name)))
+        };
+
+        let final_authority = match &document["final_authority"] {
+            Yaml::BadValue => None,
+            _ => Some(pubkey("final_authority")?),
+        };
+        let plan = JournalPlan {
+            program_sha256: field("program_sha256")?.to_string(),
+            payer: pubkey("payer")?,
+            program: pubkey("program")?,
+            buffer: pubkey("buffer")?,
+            buffer_authority: pubkey("buffer_authority")?,
+            upgrade_authority: pubkey("upgrade_authority")?,
+            max_data_len: document["max_data_len"]
+                .as_i64()
+                .and_then(|value| usize::try_from(value).ok())
+                .ok_or_else(|| invalid("max_data_len"))?,
+            final_authority,
+        };
+
+        let steps = match &document["steps"] {
+            Yaml::Array(steps) => steps
+                .iter()
+                .map(|step| {
+                    let signatures = match &step["signatures"] {
+                        Yaml::Array(signatures) => signatures
+                            .iter()
+                            .map(|signature| {
+                                signature
+                                    .as_str()
+                                    .and_then(|signature| Signature::from_str(signature).ok())
+                                    .ok_or_else(|| invalid("signatures"))
+                            })
+                            .collect::<std::result::Result<Vec<_>, _>>()?,
+                        _ => return Err(invalid("signatures")),
+                    };
+
+                    Ok(JournalStep {
+                        stage: step["stage"]
+                            .as_str()
+                            .ok_or_else(|| invalid("stage"))?
+                            .parse()?,
+                        signatures,
+                        slot: step["slot"]
+                            .as_i64()
+                            .and_then(|value| u64::try_from(value).ok())
+                            .ok_or_else(|| invalid("slot"))?,
+                    })
+                })
+                .collect::<std::result::Result<Vec<_>, InputError>>()?,
+            _ => return Err(invalid("steps").into()),
+        };
+
+        Ok(Self {
+            program_keypair: field("program_keypair")?.to_string(),
+            buffer_keypair: field("buffer_keypair")?.to_string(),
+            path,
+            plan: Some(plan),
+            steps,
+            read_only: false,
+        })
+    }
+
+    /// Keeps the file as it is, for a dry run whose steps never land.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `None` until the deployment has started.
+    pub fn plan(&self) -> Option<&JournalPlan> {
+        self.plan.as_ref()
+    }
+
+    pub fn steps(&self) -> &[JournalStep] {
+        &self.steps
+    }
+
+    /// Last step recorded as landed.
+    pub fn completed(&self) -> Option<Stage> {
+        self.steps.iter().map(|step| step.stage).max()
+    }
+
+    pub fn program_keypair(&self) -> Result<Keypair> {
+        self.keypair(&self.program_keypair)
+    }
+
+    pub fn buffer_keypair(&self) -> Result<Keypair> {
+        self.keypair(&self.buffer_keypair)
+    }
+
+    fn keypair(&self, encoded: &str) -> Result<Keypair> {
+        solana_sdk::bs58::decode(encoded)
+            .into_vec()
+            .ok()
+            .and_then(|bytes| Keypair::from_bytes(&bytes).ok())
+            .ok_or_else(|| {
+                SignerError::KeypairRead {
+                    path: self.path.display().to_string(),
+                    reason: "invalid keypair in journal".to_string(),
+                }
+                .into()
+            })
+    }
+
+    /// Records the deployment before its first transaction is sent.
+    pub fn start(&mut self, plan: JournalPlan, program: &Keypair, buffer: &Keypair) -> Result<()> {
+        self.plan = Some(plan);
+        self.program_keypair = program.to_base58_string();
+        self.buffer_keypair = buffer.to_base58_string();
+        self.write()
+    }
+
+    pub fn record(&mut self, stage: Stage, receipt: &Receipt) -> Result<()> {
+        self.steps.push(JournalStep {
+            stage,
+            signatures: receipt.signatures.clone(),
+            slot: receipt.slot,
+        });
+        self.write()
+    }
+
+    /// Removes the journal of a completed deployment.
+    pub fn finish(self) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        std::fs::remove_file(&self.path).map_err(|source| {
+            InputError::JournalWrite {
+                path: self.path.display().to_string(),
+                source,
+            }
+            .into()
+        })
+    }
+
+    fn write(&self) -> Result<()> {
+        let plan = match &self.plan {
+            Some(plan) if !self.read_only => plan,
+            _ => return Ok(()),
+        };
+
+        let mut document = YamlHash::new();
+        let mut insert = |key: &str, value: Yaml| {
+            document.insert(Yaml::String(key.to_string()), value);
+        };
+        insert("program_sha256", Yaml::String(plan.program_sha256.clone()));
+        insert("payer", Yaml::String(plan.payer.to_string()));
+        insert("program", Yaml::String(plan.program.to_string()));
+        insert(
+            "program_keypair",
+            Yaml::String(self.program_keypair.clone()),
+        );
+        insert("buffer", Yaml::String(plan.buffer.to_string()));
+        insert("buffer_keypair", Yaml::String(self.buffer_keypair.clone()));
+        insert(
+            "buffer_authority",
+            Yaml::String(plan.buffer_authority.to_string()),
+        );
+        insert(
+            "upgrade_authority",
+            Yaml::String(plan.upgrade_authority.to_string()),
+        );
+        insert("max_data_len", Yaml::Integer(plan.max_data_len as i64));
+        if let Some(final_authority) = plan.final_authority {
+            insert("final_authority", Yaml::String(final_authority.to_string()));
+        }
+        insert(
+            "steps",
+            Yaml::Array(
+                self.steps
+                    .iter()
+                    .map(|step| {
+                        let mut entry = YamlHash::new();
+                        entry.insert(
+                            Yaml::String("stage".to_string()),
+                            Yaml::String(step.stage.as_str().to_string()),
+                        );
+                        entry.insert(
+                            Yaml::String("slot".to_string()),
+                            Yaml::Integer(step.slot as i64),
+                        );
+                        entry.insert(
+                            Yaml::String("signatures".to_string()),
+                            Yaml::Array(
+                                step.signatures
+                                    .iter()
+                                    .map(|signature| Yaml::String(signature.to_string()))
+                                    .collect(),
+                            ),
+                        );
+                        Yaml::Hash(entry)
+                    })
+                    .collect(),
+            ),
+        );
+
+        let mut content = String::new();
+        YamlEmitter::new(&mut content)
+            .dump(&Yaml::Hash(document))
+            .map_err(|e| InputError::InvalidJournal(format!("{:?}", e)))?;
+        content.push('\n');
+
+        let write_error = |source| InputError::JournalWrite {
+            path: self.path.display().to_string(),
+            source,
+        };
+
+        // The journal holds the only copy of the program and buffer keypairs,
+        // so it is replaced by a complete file or not at all
+        utils::write_file_atomic(&self.path, content.as_bytes(), 0o600).map_err(write_error)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use bip39::{Language, Mnemonic, MnemonicType};
+
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::signer::keypair::{keypair_from_seed_phrase_and_passphrase, write_keypair};
+
+use crate::error::{InputError, Result, SignerError};
+
+/// Base58 alphabet, vanity prefixes outside of it never match.
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Freshly generated keypair.
+pub struct GeneratedKeypair {
+    pub keypair: Keypair,
+    /// BIP39 phrase the keypair derives from with an empty passphrase, as
+    /// `solana-keygen recover` expects
+    pub seed_phrase: Option<String>,
+    /// Keypairs tried until one matched the vanity prefix
+    pub attempts: u64,
+}
+
+/// Generates a keypair whose pubkey starts with `prefix`, grinding on every
+/// available core. With `seed_phrase` the keypair derives from a new BIP39
+/// phrase, which makes grinding considerably slower.
+pub fn generate_keypair(prefix: Option<&str>, seed_phrase: bool) -> Result<GeneratedKeypair> {
+    let prefix = prefix.unwrap_or_default().to_string();
+    if let Some(c) = prefix.chars().find(|c| !BASE58_ALPHABET.contains(*c)) {
+        return Err(
+            InputError::InvalidVanityPrefix(format!("`{}` is not a base58 character", c)).into(),
+        );
+    }
+
+    let generate = move || -> (Keypair, Option<String>) {
+        if seed_phrase {
+            let mnemonic = Mnemonic::new(MnemonicType::Words12, Language::English);
+            let keypair = keypair_from_seed_phrase_and_passphrase(mnemonic.phrase(), "")
+                .expect("keypair from a generated mnemonic");
+            (keypair, Some(mnemonic.into_phrase()))
+        } else {
+            (Keypair::new(), None)
+        }
+    };
+
+    if prefix.is_empty() {
+        let (keypair, seed_phrase) = generate();
+        return Ok(GeneratedKeypair {
+            keypair,
+            seed_phrase,
+            attempts: 1,
+        });
+    }
+
+    let found = Arc::new(AtomicBool::new(false));
+    let attempts = Arc::new(AtomicU64::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let workers = (0..threads)
+        .map(|_| {
+            let prefix = prefix.clone();
+            let found = found.clone();
+            let attempts = attempts.clone();
+            let sender = sender.clone();
+            thread::spawn(move || {
+                while !found.load(Ordering::Relaxed) {
+                    let (keypair, seed_phrase) = generate();
+                    attempts.fetch_add(1, Ordering::Relaxed);
+                    if keypair.pubkey().to_string().starts_with(&prefix)
+                        && !found.swap(true, Ordering::Relaxed)
+                    {
+                        let _ = sender.send((keypair, seed_phrase));
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(sender);
+
+    let (keypair, seed_phrase) = receiver.recv().expect("vanity keypair");
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    Ok(GeneratedKeypair {
+        keypair,
+        seed_phrase,
+        attempts: attempts.load(Ordering::Relaxed),
+    })
+}
+
+/// Writes a keypair to a new file, readable by its owner only. An existing
+/// file is never overwritten, it may hold the only copy of a program ID.
+pub fn write_new_keypair_file(keypair: &Keypair, path: &Path) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => SignerError::KeypairExists(path.display().to_string()),
+        _ => SignerError::KeypairWrite {
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        },
+    })?;
+
+    write_keypair(keypair, &mut file).map_err(|e| SignerError::KeypairWrite {
+        path: path.display().to_string(),
+        reason: e.to_string(),
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::read_keypair_file;
+
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn reject_non_base58_prefix() {
+        for prefix in ["0", "abO", "I", "l", "a-b"] {
+            match generate_keypair(Some(prefix), false) {
+                Err(Error::Input(InputError::InvalidVanityPrefix(_))) => {}
+                result => panic!(
+                    "prefix `{}`: expected an invalid prefix, got {:?}",
+                    prefix,
+                    result.map(|generated| generated.keypair.pubkey())
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn grind_prefix() {
+        let generated = generate_keypair(Some("A"), false).unwrap();
+        assert!(generated.keypair.pubkey().to_string().starts_with('A'));
+        assert!(generated.attempts >= 1);
+        assert!(generated.seed_phrase.is_none());
+    }
+
+    #[test]
+    fn derive_from_seed_phrase() {
+        let generated = generate_keypair(None, true).unwrap();
+        let seed_phrase = generated.seed_phrase.expect("seed phrase");
+        let recovered = keypair_from_seed_phrase_and_passphrase(&seed_phrase, "").unwrap();
+        assert_eq!(recovered.pubkey(), generated.keypair.pubkey());
+    }
+
+    #[test]
+    fn write_keypair_file_once() {
+        let keypair = Keypair::new();
+        let path = std::env::temp_dir().join(format!("{}-keypair.json", keypair.pubkey()));
+
+        write_new_keypair_file(&keypair, &path).unwrap();
+        assert_eq!(read_keypair_file(&path).unwrap().pubkey(), keypair.pubkey());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        match write_new_keypair_file(&Keypair::new(), &path) {
+            Err(Error::Signer(SignerError::KeypairExists(existing))) => {
+                assert_eq!(existing, path.display().to_string())
+            }
+            result => panic!("expected an existing keypair, got {:?}", result),
+        }
+        assert_eq!(read_keypair_file(&path).unwrap().pubkey(), keypair.pubkey());
+    }
+}
@@ -1,3 +1,14 @@
 pub mod client;
+pub mod deploy;
+pub mod diff;
+pub mod elf;
 pub mod error;
+pub mod everscale;
+pub mod journal;
+pub mod keygen;
+pub mod nonblocking;
+pub mod program_error;
+pub mod proposal;
+pub mod release;
+pub mod rpc;
 pub mod utils;
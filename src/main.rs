@@ -1,23 +1,56 @@
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use borsh::BorshSerialize;
-use clap::{crate_description, crate_name, crate_version, App, AppSettings, Arg, SubCommand};
-use solana_bridge::round_loader::{RelayRoundProposalEventWithLen, MAX_RELAYS, MIN_RELAYS};
+use clap::{
+    crate_description, crate_name, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand,
+};
+use solana_bridge::bridge_types::Vote;
+use solana_bridge::round_loader::{MAX_RELAYS, MIN_RELAYS};
 
 use solana_clap_utils::input_parsers::{value_of, values_of};
-use solana_clap_utils::input_validators::{is_keypair, is_valid_pubkey};
+use solana_clap_utils::input_validators::{
+    is_keypair, is_parsable, is_valid_pubkey, is_valid_signer,
+};
+use solana_clap_utils::keypair::signer_from_path;
+use solana_remote_wallet::remote_wallet::RemoteWalletManager;
+use solana_sdk::hash::hash;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::{read_keypair_file, write_keypair_file, Keypair, Signer};
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
 
-use solana_cli::client::*;
+use solana_cli::deploy::DeployPlan;
+use solana_cli::diff::ProgramDiff;
 use solana_cli::error::*;
+use solana_cli::everscale::EverAddress;
+use solana_cli::journal::Journal;
+use solana_cli::keygen::{generate_keypair, write_new_keypair_file};
+use solana_cli::nonblocking::client::*;
+use solana_cli::proposal::*;
+use solana_cli::release::{self, Lockfile, Manifest};
+use solana_cli::rpc::{DryRun, Rpc};
 use solana_cli::utils::*;
 
-fn main() -> anyhow::Result<()> {
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+async fn run() -> Result<()> {
     let app_matches = App::new(crate_name!())
         .about(crate_description!())
         .version(crate_version!())
         .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .global(true)
+                .takes_value(false)
+                .help("Simulate every transaction without sending it"),
+        )
         .subcommand(
             SubCommand::with_name("deploy")
                 .about("Deploy program ")
@@ -35,8 +68,8 @@ fn main() -> anyhow::Result<()> {
                         .validator(is_valid_pubkey)
                         .value_name("AUTHORITY")
                         .takes_value(true)
-                        .required(true)
-                        .help("Multisig address"),
+                        .required_unless("upgrade-authority")
+                        .help("Multisig address, defaults to the upgrade authority"),
                 )
                 .arg(
                     Arg::with_name("payer-keypair")
@@ -54,16 +87,71 @@ fn main() -> anyhow::Result<()> {
                         .value_name("PROGRAM_KEYPAIR")
                         .takes_value(true)
                         .required(false)
-                        .help("Path to the program keypair"),
+                        .help("Path to the program keypair, defaults to the one next to the program"),
+                )
+                .arg(
+                    Arg::with_name("grind-prefix")
+                        .long("grind-prefix")
+                        .value_name("PREFIX")
+                        .takes_value(true)
+                        .conflicts_with("program-keypair")
+                        .help("Generate a program ID starting with a base58 prefix"),
+                )
+                .arg(
+                    Arg::with_name("seed-phrase")
+                        .long("seed-phrase")
+                        .takes_value(false)
+                        .conflicts_with("program-keypair")
+                        .help("Generate the program keypair from a seed phrase and print it"),
+                )
+                .arg(
+                    Arg::with_name("program-keypair-out")
+                        .long("program-keypair-out")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .conflicts_with("program-keypair")
+                        .help("Where to write a generated program keypair, defaults to the one next to the program"),
                 )
                 .arg(
                     Arg::with_name("program-size")
                         .long("program-size")
+                        .validator(is_parsable::<usize>)
                         .value_name("PROGRAM_SIZE")
                         .takes_value(true)
                         .required(true)
                         .help("Program size"),
-                ),
+                )
+                .args(&authority_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("resume")
+                .about("Resume an interrupted deployment from its journal")
+                .arg(
+                    Arg::with_name("program-path")
+                        .long("program-path")
+                        .value_name("PROGRAM_PATH")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Path to the program"),
+                )
+                .arg(
+                    Arg::with_name("journal")
+                        .long("journal")
+                        .value_name("JOURNAL")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Path to the journal, defaults to the one next to the program"),
+                )
+                .arg(
+                    Arg::with_name("payer-keypair")
+                        .long("payer-keypair")
+                        .validator(is_keypair)
+                        .value_name("PAYER_KEYPAIR")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Path to the payer keypair"),
+                )
+                .args(&authority_args()),
         )
         .subcommand(
             SubCommand::with_name("upload-program-buffer")
@@ -93,6 +181,15 @@ fn main() -> anyhow::Result<()> {
                         .takes_value(true)
                         .required(false)
                         .help("Path to the payer keypair"),
+                )
+                .arg(
+                    Arg::with_name("buffer-authority-keypair")
+                        .long("buffer-authority-keypair")
+                        .validator(is_keypair)
+                        .value_name("BUFFER_AUTHORITY_KEYPAIR")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Path to the keypair signing the buffer writes, defaults to the payer"),
                 ),
         )
         .subcommand(
@@ -116,6 +213,15 @@ fn main() -> anyhow::Result<()> {
                         .required(false)
                         .help("Path to the current authority keypair"),
                 )
+                .arg(
+                    Arg::with_name("payer-keypair")
+                        .long("payer-keypair")
+                        .validator(is_keypair)
+                        .value_name("PAYER_KEYPAIR")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Path to the fee payer keypair, defaults to the current authority"),
+                )
                 .arg(
                     Arg::with_name("new-authority")
                         .long("new-authority")
@@ -129,280 +235,888 @@ fn main() -> anyhow::Result<()> {
         .subcommand(
             SubCommand::with_name("create-relay-round")
                 .about("Set a program's authority.")
+                .args(&proposal_args())
                 .arg(
-                    Arg::with_name("event_timestamp")
-                        .long("event-timestamp")
-                        .value_name("EVENT_TIMESTAMP")
+                    Arg::with_name("round_number")
+                        .long("round-number")
+                        .validator(is_parsable::<u32>)
+                        .value_name("ROUND_NUMBER")
                         .takes_value(true)
-                        .required(true)
-                        .help("Everscale event timestamp"),
+                        .required(false)
+                        .help(
+                            "Current Relay Round number [default: read from round loader settings]",
+                        ),
                 )
                 .arg(
-                    Arg::with_name("transaction_lt")
-                        .long("transaction-lt")
-                        .value_name("TRANSACTION_LT")
+                    Arg::with_name("payer-keypair")
+                        .long("payer-keypair")
+                        .validator(is_keypair)
+                        .value_name("PAYER_KEYPAIR")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Path to the payer keypair"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show-proposal")
+                .about("Show relay round proposal")
+                .arg(
+                    Arg::with_name("proposal")
+                        .long("proposal")
+                        .validator(is_valid_pubkey)
+                        .value_name("PROPOSAL")
                         .takes_value(true)
                         .required(true)
-                        .help("Everscale event transaction lt"),
+                        .help("Proposal address"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("vote-relay-round")
+                .about("Vote for relay round proposal")
+                .arg(
+                    Arg::with_name("proposal")
+                        .long("proposal")
+                        .validator(is_valid_pubkey)
+                        .value_name("PROPOSAL")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Proposal address"),
                 )
                 .arg(
-                    Arg::with_name("configuration")
-                        .long("configuration")
-                        .value_name("CONFIGURATION")
+                    Arg::with_name("reject")
+                        .long("reject")
+                        .takes_value(false)
+                        .help("Vote against the proposal"),
+                )
+                .arg(
+                    Arg::with_name("relay-keypair")
+                        .long("relay-keypair")
+                        .validator(is_keypair)
+                        .value_name("RELAY_KEYPAIR")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Path to the relay keypair"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("execute-relay-round")
+                .about("Execute approved relay round proposal")
+                .arg(
+                    Arg::with_name("proposal")
+                        .long("proposal")
+                        .validator(is_valid_pubkey)
+                        .value_name("PROPOSAL")
                         .takes_value(true)
                         .required(true)
-                        .help("Everscale event configuration"),
+                        .help("Proposal address"),
                 )
+                .arg(
+                    Arg::with_name("payer-keypair")
+                        .long("payer-keypair")
+                        .validator(is_keypair)
+                        .value_name("PAYER_KEYPAIR")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Path to the payer keypair"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show-relay-round")
+                .about("Show relay round")
+                .arg(
+                    Arg::with_name("round")
+                        .long("round")
+                        .validator(is_parsable::<u32>)
+                        .value_name("ROUND")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Relay Round number [default: current round]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show-round-loader-settings").about("Show round loader settings"),
+        )
+        .subcommand(
+            SubCommand::with_name("compute-proposal")
+                .about("Compute relay round proposal address and payload offline")
+                .args(&proposal_args())
                 .arg(
                     Arg::with_name("round_number")
                         .long("round-number")
+                        .validator(is_parsable::<u32>)
                         .value_name("ROUND_NUMBER")
                         .takes_value(true)
                         .required(true)
                         .help("Current Relay Round number"),
-                )
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("watch-proposal")
+                .about("Follow relay round proposal until it is executed")
                 .arg(
-                    Arg::with_name("proposal_round_number")
-                        .long("proposal-round-number")
-                        .value_name("PROPOSAL_ROUND_NUMBER")
+                    Arg::with_name("proposal")
+                        .index(1)
+                        .validator(is_valid_pubkey)
+                        .value_name("ADDRESS")
                         .takes_value(true)
                         .required(true)
-                        .help("Relay Round number in proposal"),
+                        .help("Proposal address"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff-program")
+                .about("Compare a deployed program with a local build")
+                .arg(
+                    Arg::with_name("program")
+                        .index(1)
+                        .validator(is_valid_pubkey)
+                        .value_name("PROGRAM_ID")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Deployed program address"),
                 )
                 .arg(
-                    Arg::with_name("proposal_relays")
-                        .long("proposal-relays")
-                        .value_name("PROPOSAL_RELAYS")
+                    Arg::with_name("program-path")
+                        .index(2)
+                        .value_name("ELF")
                         .takes_value(true)
                         .required(true)
-                        .min_values(MIN_RELAYS as u64)
-                        .max_values(MAX_RELAYS as u64)
-                        .help("List of Relays in proposal"),
+                        .help("Path to the candidate program"),
                 )
                 .arg(
-                    Arg::with_name("proposal_round_end")
-                        .long("proposal-round-end")
-                        .value_name("PROPOSAL_ROUND_END")
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Where to dump the deployed program, defaults to `<PROGRAM_ID>.so`"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("release")
+                .about("Deploy or upgrade the programs listed in a release manifest")
+                .arg(
+                    Arg::with_name("manifest")
+                        .long("manifest")
+                        .value_name("MANIFEST")
                         .takes_value(true)
                         .required(true)
-                        .help("Round end value in proposal"),
+                        .help("Path to the release manifest"),
+                )
+                .arg(
+                    Arg::with_name("lockfile")
+                        .long("lockfile")
+                        .value_name("LOCKFILE")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Path to the lockfile, defaults to the manifest path with a `.lock` extension"),
                 )
                 .arg(
-                    Arg::with_name("payer_keypair")
+                    Arg::with_name("check")
+                        .long("check")
+                        .takes_value(false)
+                        .help("Verify that the chain matches the lockfile instead of releasing"),
+                )
+                .arg(
+                    Arg::with_name("payer-keypair")
                         .long("payer-keypair")
                         .validator(is_keypair)
                         .value_name("PAYER_KEYPAIR")
                         .takes_value(true)
                         .required(false)
                         .help("Path to the payer keypair"),
+                )
+                .arg(
+                    Arg::with_name("authority-keypair")
+                        .long("authority-keypair")
+                        .validator(is_keypair)
+                        .value_name("AUTHORITY_KEYPAIR")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Path to the keypair signing buffers and upgrades, defaults to the payer"),
                 ),
         )
         .get_matches();
 
-    let connection = establish_connection()?;
+    // Offline commands
+    if let ("compute-proposal", Some(arg_matches)) = app_matches.subcommand() {
+        let round_number = value_of::<u32>(arg_matches, "round_number")
+            .ok_or(InputError::InvalidArgument("round-number"))?;
+
+        let input = read_proposal_input(arg_matches)?;
+        let payload = input.proposal().try_to_vec()?;
+
+        println!("Proposal address: {}", input.address(round_number)?);
+        println!("Payload (hex): {}", hex::encode(&payload));
+        println!("Payload (base64): {}", base64::encode(&payload));
+        println!("Payload hash (sha256): {}", hash(&payload));
+
+        return Ok(());
+    }
+
+    let rpc_client = establish_connection()?;
     println!(
         "Connected to remote solana node running version ({}).",
-        connection.get_version()?
+        rpc_client.get_version().await?
     );
 
+    let connection: Box<dyn Rpc> = if app_matches.is_present("dry-run") {
+        println!("Dry run: transactions are simulated and not sent.");
+        Box::new(DryRun::new(rpc_client))
+    } else {
+        Box::new(rpc_client)
+    };
+
     let (sub_command, sub_matches) = app_matches.subcommand();
 
     match (sub_command, sub_matches) {
         ("deploy", Some(arg_matches)) => {
-            let payer = match value_of::<String>(arg_matches, "payer-keypair") {
-                None => get_payer()?,
-                Some(path) => read_keypair_file(&path)
-                    .map_err(|_| anyhow::Error::new(Error::KeypairReadError))?,
-            };
+            let payer = read_keypair(arg_matches, "payer-keypair")?;
             println!("Deploying with key: {}", payer.pubkey());
 
-            let buffer = Keypair::new();
-            println!("Buffer key: {}", buffer.pubkey());
+            let program_path = value_of::<String>(arg_matches, "program-path")
+                .ok_or(InputError::InvalidArgument("program-path"))?;
 
-            let authority_pubkey = Pubkey::from_str(
-                value_of::<String>(arg_matches, "authority")
-                    .ok_or(Error::InvalidPubkey)?
-                    .as_str(),
-            )?;
-            println!("Program authority: {}", authority_pubkey);
-
-            let program_path =
-                value_of::<String>(arg_matches, "program-path").ok_or(Error::InvalidProgramPath)?;
+            let keypair_file = get_keypair_file(&program_path);
+            let grind_prefix = value_of::<String>(arg_matches, "grind-prefix");
+            let seed_phrase = arg_matches.is_present("seed-phrase");
+            let generate = grind_prefix.is_some() || seed_phrase;
+            let keypair_out = value_of::<String>(arg_matches, "program-keypair-out")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| keypair_file.clone());
+            // `cargo build-sbf` always writes a keypair next to the program,
+            // a generated one goes elsewhere instead of replacing it
+            let generates_keypair =
+                !arg_matches.is_present("program-keypair") && (generate || !keypair_file.exists());
+            if generates_keypair && keypair_out.exists() {
+                return Err(
+                    InputError::KeypairOutputExists(keypair_out.display().to_string()).into(),
+                );
+            }
 
             let program_data = read_elf(&program_path)?;
+            let mut journal = Journal::new(get_journal_file(&program_path))?;
+            // A dry run lands nothing, so it leaves nothing to resume
+            if app_matches.is_present("dry-run") {
+                journal = journal.read_only();
+            } else {
+                println!("Journal: {}", journal.path().display());
+            }
 
             let max_data_len = match value_of::<usize>(arg_matches, "program-size") {
                 Some(len) => len * 1000,
                 None => program_data.len(),
             };
-
-            create_buffer(&payer, &buffer, &payer.pubkey(), max_data_len, &connection)?;
-
-            write_buffer(&payer, &buffer.pubkey(), &program_data, &connection)?;
+            // The buffer rent would be paid before the write fails
+            if max_data_len < program_data.len() {
+                return Err(InputError::ProgramSizeTooSmall {
+                    program_len: program_data.len(),
+                    max_data_len,
+                }
+                .into());
+            }
 
             let program = match value_of::<String>(arg_matches, "program-keypair") {
+                Some(path) => read_keypair_file(&path).map_err(|e| SignerError::KeypairRead {
+                    path,
+                    reason: e.to_string(),
+                })?,
+                // Same keypair `cargo build-sbf` writes next to the program
+                None if keypair_file.exists() && !generate => {
+                    println!("Program keypair: {}", keypair_file.display());
+                    read_keypair_file(&keypair_file).map_err(|e| SignerError::KeypairRead {
+                        path: keypair_file.display().to_string(),
+                        reason: e.to_string(),
+                    })?
+                }
                 None => {
-                    let program = Keypair::new();
-                    let keypair_file = get_keypair_file(&program_path);
-                    write_keypair_file(&program, keypair_file)
-                        .map_err(|_| anyhow::Error::new(Error::KeypairReadError))?;
-                    program
+                    if let Some(prefix) = &grind_prefix {
+                        println!("Grinding program ID starting with `{}`", prefix);
+                    }
+                    let generated = generate_keypair(grind_prefix.as_deref(), seed_phrase)?;
+                    if app_matches.is_present("dry-run") {
+                        // Nothing is deployed, so the program ID is thrown away
+                        println!(
+                            "Program keypair: not written in a dry run ({} attempts)",
+                            generated.attempts
+                        );
+                    } else {
+                        // Checked up front, writing the file checks again
+                        write_new_keypair_file(&generated.keypair, &keypair_out)?;
+                        println!(
+                            "Program keypair: {} ({} attempts)",
+                            keypair_out.display(),
+                            generated.attempts
+                        );
+                        if let Some(seed_phrase) = &generated.seed_phrase {
+                            print_seed_phrase(seed_phrase);
+                        }
+                    }
+                    generated.keypair
                 }
-                Some(path) => read_keypair_file(&path)
-                    .map_err(|_| anyhow::Error::new(Error::KeypairReadError))?,
             };
+            println!("Program ID: {}", program.pubkey());
 
-            deploy(
-                &payer,
-                &program,
-                &buffer.pubkey(),
-                max_data_len,
-                &connection,
-            )?;
+            let buffer_authority = read_optional_keypair(arg_matches, "buffer-authority-keypair")?;
+            let buffer_authority = buffer_authority.as_ref().unwrap_or(&payer);
+            println!("Buffer authority: {}", buffer_authority.pubkey());
+
+            let mut wallet_manager = None;
+            let upgrade_authority =
+                read_optional_signer(arg_matches, "upgrade-authority", &mut wallet_manager)?;
+            let upgrade_authority = match &upgrade_authority {
+                Some(signer) => signer.as_ref(),
+                None => buffer_authority,
+            };
+            println!("Upgrade authority: {}", upgrade_authority.pubkey());
 
-            set_program_authority(&payer, &program.pubkey(), &authority_pubkey, &connection)?;
+            let authority_pubkey = if arg_matches.is_present("authority") {
+                pubkey_of(arg_matches, "authority")?
+            } else {
+                upgrade_authority.pubkey()
+            };
+            println!("Program authority: {}", authority_pubkey);
+            if authority_pubkey == upgrade_authority.pubkey() {
+                println!("Deploy names the program authority directly, no handoff");
+            } else {
+                println!(
+                    "Program is handed over to ({}) once deployed",
+                    authority_pubkey
+                );
+            }
+
+            let receipt = DeployPlan::new(&program_data, &payer, &program)
+                .buffer_authority(buffer_authority)
+                .upgrade_authority(upgrade_authority)
+                .max_data_len(max_data_len)
+                .final_authority(authority_pubkey)
+                .journal(journal)
+                .deployer(&*connection)
+                .on_checkpoint(print_checkpoint)
+                .run()
+                .await?;
+            print_deploy_receipt(&receipt);
         }
-        ("upload-program-buffer", Some(arg_matches)) => {
-            let payer = match value_of::<String>(arg_matches, "payer-keypair") {
-                None => get_payer()?,
-                Some(path) => read_keypair_file(&path)
-                    .map_err(|_| anyhow::Error::new(Error::KeypairReadError))?,
+        ("resume", Some(arg_matches)) => {
+            let payer = read_keypair(arg_matches, "payer-keypair")?;
+
+            let program_path = value_of::<String>(arg_matches, "program-path")
+                .ok_or(InputError::InvalidArgument("program-path"))?;
+            let program_data = read_elf(&program_path)?;
+
+            let journal_path = match value_of::<String>(arg_matches, "journal") {
+                Some(path) => PathBuf::from(path),
+                None => get_journal_file(&program_path),
             };
+            let mut journal = Journal::load(journal_path)?;
+            // Simulated steps are neither recorded nor complete the journal
+            if app_matches.is_present("dry-run") {
+                journal = journal.read_only();
+            }
+            let plan = journal
+                .plan()
+                .cloned()
+                .ok_or_else(|| InputError::InvalidJournal("deployment not started".to_string()))?;
+            if plan.payer != payer.pubkey() {
+                return Err(InputError::InvalidJournal(format!(
+                    "deployment was started by payer ({})",
+                    plan.payer
+                ))
+                .into());
+            }
+
+            let buffer_authority = read_optional_keypair(arg_matches, "buffer-authority-keypair")?;
+            let buffer_authority = buffer_authority.as_ref().unwrap_or(&payer);
+            let mut wallet_manager = None;
+            let upgrade_authority =
+                read_optional_signer(arg_matches, "upgrade-authority", &mut wallet_manager)?;
+            let upgrade_authority = match &upgrade_authority {
+                Some(signer) => signer.as_ref(),
+                None => buffer_authority,
+            };
+            if plan.buffer_authority != buffer_authority.pubkey()
+                || plan.upgrade_authority != upgrade_authority.pubkey()
+            {
+                return Err(InputError::InvalidJournal(format!(
+                    "deployment was started with buffer authority ({}) and upgrade authority ({})",
+                    plan.buffer_authority, plan.upgrade_authority
+                ))
+                .into());
+            }
+
+            print_header("Resuming deployment");
+            println!("Journal: {}", journal.path().display());
+            println!("Program: {}", plan.program);
+            println!("Buffer: {}", plan.buffer);
+            for step in journal.steps() {
+                println!("Completed: {:?} (slot {})", step.stage, step.slot);
+            }
+
+            let program = journal.program_keypair()?;
+            let buffer = journal.buffer_keypair()?;
+
+            let mut deploy_plan = DeployPlan::new(&program_data, &payer, &program)
+                .buffer(&buffer)
+                .buffer_authority(buffer_authority)
+                .upgrade_authority(upgrade_authority)
+                .max_data_len(plan.max_data_len);
+            if let Some(final_authority) = plan.final_authority {
+                deploy_plan = deploy_plan.final_authority(final_authority);
+            }
+
+            let receipt = deploy_plan
+                .journal(journal)
+                .deployer(&*connection)
+                .on_checkpoint(print_checkpoint)
+                .run()
+                .await?;
+            print_deploy_receipt(&receipt);
+        }
+        ("upload-program-buffer", Some(arg_matches)) => {
+            let payer = read_keypair(arg_matches, "payer-keypair")?;
             println!("Uploading with key: {}", payer.pubkey());
 
             let buffer = Keypair::new();
             println!("Buffer key: {}", buffer.pubkey());
 
-            let authority_pubkey = Pubkey::from_str(
-                value_of::<String>(arg_matches, "authority")
-                    .ok_or(Error::InvalidPubkey)?
-                    .as_str(),
-            )?;
+            let buffer_authority = read_optional_keypair(arg_matches, "buffer-authority-keypair")?;
+            let buffer_authority = buffer_authority.as_ref().unwrap_or(&payer);
+
+            let authority_pubkey = pubkey_of(arg_matches, "authority")?;
             println!("Buffer authority: {}", authority_pubkey);
 
-            let program_path =
-                value_of::<String>(arg_matches, "program-path").ok_or(Error::InvalidProgramPath)?;
+            let program_path = value_of::<String>(arg_matches, "program-path")
+                .ok_or(InputError::InvalidArgument("program-path"))?;
 
             let program_data = read_elf(&program_path)?;
 
-            create_buffer(
+            let receipt = create_buffer(
                 &payer,
                 &buffer,
-                &payer.pubkey(),
+                &buffer_authority.pubkey(),
                 program_data.len(),
-                &connection,
-            )?;
+                &*connection,
+            )
+            .await?;
+            print_receipt(&receipt);
 
-            write_buffer(&payer, &buffer.pubkey(), &program_data, &connection)?;
-
-            set_buffer_authority(
+            let receipt = write_buffer(
                 &payer,
+                buffer_authority,
+                &buffer.pubkey(),
+                &program_data,
+                &*connection,
+            )
+            .await?;
+            print_receipt(&receipt);
+
+            let receipt = set_buffer_authority(
                 &payer,
+                buffer_authority,
                 &buffer.pubkey(),
                 &authority_pubkey,
-                &connection,
-            )?;
+                &*connection,
+            )
+            .await?;
+            print_receipt(&receipt);
         }
         ("set-program-authority", Some(arg_matches)) => {
-            let current_authority =
-                match value_of::<String>(arg_matches, "current-authority-keypair") {
-                    None => get_payer()?,
-                    Some(path) => read_keypair_file(&path)
-                        .map_err(|_| anyhow::Error::new(Error::KeypairReadError))?,
-                };
+            let current_authority = read_keypair(arg_matches, "current-authority-keypair")?;
             println!("Current authority: {}", current_authority.pubkey());
 
-            let program_pubkey = Pubkey::from_str(
-                value_of::<String>(arg_matches, "program")
-                    .ok_or(Error::InvalidPubkey)?
-                    .as_str(),
-            )?;
+            let payer = read_optional_keypair(arg_matches, "payer-keypair")?;
+            let payer = payer.as_ref().unwrap_or(&current_authority);
+
+            let program_pubkey = pubkey_of(arg_matches, "program")?;
             println!("Program: {}", program_pubkey);
 
-            let new_authority_pubkey = Pubkey::from_str(
-                value_of::<String>(arg_matches, "new-authority")
-                    .ok_or(Error::InvalidPubkey)?
-                    .as_str(),
-            )?;
+            let new_authority_pubkey = pubkey_of(arg_matches, "new-authority")?;
             println!("Program: {}", program_pubkey);
 
-            set_program_authority(
+            let receipt = set_program_authority(
+                payer,
                 &current_authority,
                 &program_pubkey,
                 &new_authority_pubkey,
-                &connection,
-            )?;
+                &*connection,
+            )
+            .await?;
+            print_receipt(&receipt);
         }
         ("create-relay-round", Some(arg_matches)) => {
-            let payer = match value_of::<String>(arg_matches, "payer-keypair") {
-                None => get_payer()?,
-                Some(path) => read_keypair_file(&path)
-                    .map_err(|_| anyhow::Error::new(Error::KeypairReadError))?,
-            };
+            let payer = read_keypair(arg_matches, "payer-keypair")?;
             println!("Creating proposal with key: {}", payer.pubkey());
 
+            let round_number = match value_of::<u32>(arg_matches, "round_number") {
+                Some(round_number) => round_number,
+                None => {
+                    get_round_loader_settings(&*connection)
+                        .await?
+                        .current_round_number
+                }
+            };
+            println!("Current Relay Round number: {}", round_number);
+
+            let input = read_proposal_input(arg_matches)?;
+
+            let proposal_pubkey = input.address(round_number)?;
+            println!("Proposal address: {}", proposal_pubkey);
+
+            let receipt = propose_relay_round(&payer, round_number, &input, &*connection).await?;
+            print_receipt(&receipt);
+        }
+        ("show-proposal", Some(arg_matches)) => {
+            let proposal_pubkey = pubkey_of(arg_matches, "proposal")?;
+            println!("Proposal address: {}", proposal_pubkey);
+
+            let proposal = get_relay_round_proposal(&proposal_pubkey, &*connection).await?;
+            let relay_round = get_relay_round(proposal.round_number, &*connection)
+                .await
+                .ok();
+
+            print_header("Relay Round Proposal");
+            print_proposal(&proposal, relay_round.as_ref());
+        }
+        ("vote-relay-round", Some(arg_matches)) => {
+            let relay = read_keypair(arg_matches, "relay-keypair")?;
+            println!("Voting with key: {}", relay.pubkey());
+
+            let proposal_pubkey = pubkey_of(arg_matches, "proposal")?;
+            println!("Proposal address: {}", proposal_pubkey);
+
+            let vote = match arg_matches.is_present("reject") {
+                true => Vote::Reject,
+                false => Vote::Confirm,
+            };
+            println!("Vote: {:?}", vote);
+
+            let receipt =
+                vote_for_relay_round_proposal(&relay, &proposal_pubkey, vote, &*connection).await?;
+            print_receipt(&receipt);
+
+            let proposal = get_relay_round_proposal(&proposal_pubkey, &*connection).await?;
+            let relay_round = get_relay_round(proposal.round_number, &*connection).await?;
+            print_votes(&proposal, Some(&relay_round));
+        }
+        ("execute-relay-round", Some(arg_matches)) => {
+            let payer = read_keypair(arg_matches, "payer-keypair")?;
+            println!("Executing proposal with key: {}", payer.pubkey());
+
+            let proposal_pubkey = pubkey_of(arg_matches, "proposal")?;
+            println!("Proposal address: {}", proposal_pubkey);
+
+            let receipt =
+                execute_relay_round_proposal(&payer, &proposal_pubkey, &*connection).await?;
+            print_receipt(&receipt);
+
+            let proposal = get_relay_round_proposal(&proposal_pubkey, &*connection).await?;
+            let relay_round = get_relay_round(proposal.event.data.round_num, &*connection).await?;
+
+            print_header("Relay Round");
+            print_relay_round(&relay_round);
+        }
+        ("show-relay-round", Some(arg_matches)) => {
+            let round_number = match value_of::<u32>(arg_matches, "round") {
+                Some(round_number) => round_number,
+                None => {
+                    get_round_loader_settings(&*connection)
+                        .await?
+                        .current_round_number
+                }
+            };
+
+            let relay_round = get_relay_round(round_number, &*connection).await?;
+            println!(
+                "Relay Round address: {}",
+                solana_bridge::round_loader::get_relay_round_address(round_number)
+            );
+
+            print_header("Relay Round");
+            print_relay_round(&relay_round);
+        }
+        ("show-round-loader-settings", Some(_)) => {
+            let settings = get_round_loader_settings(&*connection).await?;
+            println!(
+                "Settings address: {}",
+                solana_bridge::round_loader::get_settings_address()
+            );
+
+            print_header("Round Loader Settings");
+            print_round_loader_settings(&settings);
+        }
+        ("watch-proposal", Some(arg_matches)) => {
+            let proposal_pubkey = pubkey_of(arg_matches, "proposal")?;
+            println!("Proposal address: {}", proposal_pubkey);
+
+            let websocket_url = get_ws_url()?;
+
+            print_header("Watching Relay Round Proposal");
+            watch_relay_round_proposal(&proposal_pubkey, &websocket_url, &*connection).await?;
+            println!("Proposal executed");
+        }
+        ("diff-program", Some(arg_matches)) => {
+            let program_pubkey = pubkey_of(arg_matches, "program")?;
+            println!("Program: {}", program_pubkey);
+
+            let program_path = value_of::<String>(arg_matches, "program-path")
+                .ok_or(InputError::InvalidArgument("program-path"))?;
+            let candidate = read_elf(&program_path)?;
+
+            let program_data = get_program_data(&program_pubkey, &*connection)
+                .await?
+                .ok_or(OnChainError::AccountNotFound(program_pubkey))?;
+            println!("ProgramData: {}", program_data.address);
+            println!("Deployed at slot: {}", program_data.slot);
+
+            let diff = ProgramDiff::new(&program_data.data, &candidate, program_data.data.len())?;
+
+            let output = match value_of::<String>(arg_matches, "output") {
+                Some(path) => PathBuf::from(path),
+                None => PathBuf::from(format!("{}.so", program_pubkey)),
+            };
+            std::fs::write(&output, &program_data.data[..diff.deployed_len]).map_err(|source| {
+                InputError::FileWrite {
+                    path: output.display().to_string(),
+                    source,
+                }
+            })?;
+            println!("Deployed program dumped to {}", output.display());
+
+            print_program_diff(&diff);
+        }
+        ("release", Some(arg_matches)) => {
+            let manifest_path = PathBuf::from(
+                value_of::<String>(arg_matches, "manifest")
+                    .ok_or(InputError::InvalidArgument("manifest"))?,
+            );
+            let lockfile_path = match value_of::<String>(arg_matches, "lockfile") {
+                Some(path) => PathBuf::from(path),
+                None => manifest_path.with_extension("lock"),
+            };
+
+            if arg_matches.is_present("check") {
+                let lockfile = Lockfile::from_file(&lockfile_path)?;
+
+                print_header("Checking release");
+                let mismatches = release::check(&lockfile, &*connection).await?;
+                for mismatch in &mismatches {
+                    println!("{}: {}", mismatch.name, mismatch.reason);
+                }
+                if !mismatches.is_empty() {
+                    return Err(OnChainError::LockfileMismatch(mismatches.len()).into());
+                }
+                println!(
+                    "Chain matches the lockfile ({} programs)",
+                    lockfile.programs.len()
+                );
+            } else {
+                let manifest = Manifest::from_file(&manifest_path)?;
+
+                let payer = read_keypair(arg_matches, "payer-keypair")?;
+                println!("Releasing with key: {}", payer.pubkey());
+
+                let authority = read_optional_keypair(arg_matches, "authority-keypair")?;
+                let authority = authority.as_ref().unwrap_or(&payer);
+                println!("Release authority: {}", authority.pubkey());
+
+                // Simulated signatures are no release record
+                let dry_run = app_matches.is_present("dry-run");
+                let record = if dry_run {
+                    None
+                } else {
+                    Some(lockfile_path.as_path())
+                };
+
+                let lockfile =
+                    release::release(&manifest, &payer, authority, record, &*connection).await?;
+                if dry_run {
+                    println!("Simulated ({}) programs", manifest.programs.len());
+                } else {
+                    println!(
+                        "Released ({}) programs, lockfile: {}",
+                        lockfile.programs.len(),
+                        lockfile_path.display()
+                    );
+                }
+            }
+        }
+        _ => {}
+    };
+
+    Ok(())
+}
+
+/// Deployment signer arguments shared by `deploy` and `resume`.
+fn authority_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("buffer-authority-keypair")
+            .long("buffer-authority-keypair")
+            .validator(is_keypair)
+            .value_name("BUFFER_AUTHORITY_KEYPAIR")
+            .takes_value(true)
+            .required(false)
+            .help("Path to the keypair signing the buffer writes, defaults to the payer"),
+        Arg::with_name("upgrade-authority")
+            .long("upgrade-authority")
+            .validator(is_valid_signer)
+            .value_name("UPGRADE_AUTHORITY_SIGNER")
+            .takes_value(true)
+            .required(false)
+            .help(
+                "Signer of the deployment, e.g. a keypair path or `usb://ledger`, \
+                 defaults to the buffer authority",
+            ),
+    ]
+}
+
+/// Relay round proposal arguments shared by the commands that build one.
+fn proposal_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("event_timestamp")
+            .long("event-timestamp")
+            .value_name("EVENT_TIMESTAMP")
+            .takes_value(true)
+            .required_unless("proposal_file")
+            .conflicts_with("proposal_file")
+            .help("Everscale event timestamp"),
+        Arg::with_name("transaction_lt")
+            .long("transaction-lt")
+            .value_name("TRANSACTION_LT")
+            .takes_value(true)
+            .required_unless("proposal_file")
+            .conflicts_with("proposal_file")
+            .help("Everscale event transaction lt"),
+        Arg::with_name("configuration")
+            .long("configuration")
+            .value_name("CONFIGURATION")
+            .takes_value(true)
+            .required_unless("proposal_file")
+            .conflicts_with("proposal_file")
+            .help("Everscale event configuration address, raw or user-friendly"),
+        Arg::with_name("proposal_round_number")
+            .long("proposal-round-number")
+            .value_name("PROPOSAL_ROUND_NUMBER")
+            .takes_value(true)
+            .required_unless("proposal_file")
+            .conflicts_with("proposal_file")
+            .help("Relay Round number in proposal"),
+        Arg::with_name("proposal_relays")
+            .long("proposal-relays")
+            .value_name("PROPOSAL_RELAYS")
+            .takes_value(true)
+            .required_unless("proposal_file")
+            .conflicts_with("proposal_file")
+            .min_values(MIN_RELAYS as u64)
+            .max_values(MAX_RELAYS as u64)
+            .help("List of Relays in proposal"),
+        Arg::with_name("proposal_round_end")
+            .long("proposal-round-end")
+            .value_name("PROPOSAL_ROUND_END")
+            .takes_value(true)
+            .required_unless("proposal_file")
+            .conflicts_with("proposal_file")
+            .help("Round end value in proposal"),
+        Arg::with_name("proposal_file")
+            .long("proposal-file")
+            .value_name("PROPOSAL_FILE")
+            .takes_value(true)
+            .required(false)
+            .help("Path to a JSON or YAML file with the proposal"),
+    ]
+}
+
+fn read_proposal_input(arg_matches: &ArgMatches) -> Result<ProposalInput> {
+    let input = match value_of::<String>(arg_matches, "proposal_file") {
+        Some(path) => ProposalInput::from_file(&path)?,
+        None => {
             let event_timestamp = value_of::<u32>(arg_matches, "event_timestamp")
-                .ok_or(Error::InvalidEventTimestamp)?;
+                .ok_or(InputError::InvalidArgument("event-timestamp"))?;
 
             let event_transaction_lt = value_of::<u64>(arg_matches, "transaction_lt")
-                .ok_or(Error::InvalidTransactionLt)?;
-
-            let event_configuration = {
-                let bytes = hex::decode(
-                    value_of::<String>(arg_matches, "configuration")
-                        .ok_or(Error::InvalidConfiguration)?
-                        .as_str(),
-                )?;
-                Pubkey::try_from(bytes.as_slice())?
-            };
+                .ok_or(InputError::InvalidArgument("transaction-lt"))?;
 
-            let round_number =
-                value_of::<u32>(arg_matches, "round_number").ok_or(Error::InvalidRoundNumber)?;
+            let event_configuration = value_of::<String>(arg_matches, "configuration")
+                .ok_or(InputError::InvalidArgument("configuration"))?
+                .parse::<EverAddress>()?
+                .to_pubkey()?;
 
             let proposal_round_num = value_of::<u32>(arg_matches, "proposal_round_number")
-                .ok_or(Error::InvalidProposalRoundNumber)?;
+                .ok_or(InputError::InvalidArgument("proposal-round-number"))?;
 
             let proposal_relays = values_of::<String>(arg_matches, "proposal_relays")
-                .ok_or(Error::InvalidProposalRelays)?;
+                .ok_or(InputError::InvalidArgument("proposal-relays"))?;
 
             let mut relays = vec![];
             for proposal_relay in proposal_relays {
-                let bytes = hex::decode(&proposal_relay)?;
-                relays.push(Pubkey::try_from(bytes.as_slice())?);
+                relays.push(parse_pubkey(&proposal_relay)?);
             }
 
             let proposal_round_end = value_of::<u32>(arg_matches, "proposal_round_end")
-                .ok_or(Error::InvalidRoundNumber)?;
-
-            let proposal =
-                RelayRoundProposalEventWithLen::new(proposal_round_num, relays, proposal_round_end);
+                .ok_or(InputError::InvalidArgument("proposal-round-end"))?;
 
-            let proposal_pubkey = solana_bridge::round_loader::get_proposal_address(
-                round_number,
+            let input = ProposalInput {
                 event_timestamp,
                 event_transaction_lt,
-                &event_configuration,
-                &proposal.data.try_to_vec()?,
-            );
+                event_configuration,
+                round_number: proposal_round_num,
+                round_end: proposal_round_end,
+                relays,
+            };
+            input.validate()?;
+            input
+        }
+    };
 
-            println!("Proposal address: {}", proposal_pubkey);
+    Ok(input)
+}
 
-            create_relay_round_proposal(
-                &payer,
-                round_number,
-                event_timestamp,
-                event_transaction_lt,
-                event_configuration,
-                &proposal,
-                &connection,
-            )?;
+/// Reads the keypair passed in `name`, falling back to the keypair from the
+/// solana config.
+fn read_keypair(arg_matches: &ArgMatches, name: &str) -> Result<Keypair> {
+    match value_of::<String>(arg_matches, name) {
+        None => get_payer(),
+        Some(path) => read_keypair_file(&path).map_err(|e| {
+            SignerError::KeypairRead {
+                path,
+                reason: e.to_string(),
+            }
+            .into()
+        }),
+    }
+}
 
-            write_relay_round_proposal(&payer, &proposal_pubkey, &proposal, &connection)?;
+/// Keypair of an optional argument, `None` when it is not given.
+fn read_optional_keypair(arg_matches: &ArgMatches, name: &str) -> Result<Option<Keypair>> {
+    if arg_matches.is_present(name) {
+        read_keypair(arg_matches, name).map(Some)
+    } else {
+        Ok(None)
+    }
+}
 
-            finalize_relay_round_proposal(&payer, &proposal_pubkey, round_number, &connection)?;
-        }
-        _ => {}
+/// Signer of an optional argument: a keypair file, a hardware wallet such as
+/// `usb://ledger`, or any other source `solana-clap-utils` understands.
+fn read_optional_signer(
+    arg_matches: &ArgMatches,
+    name: &str,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) -> Result<Option<Box<dyn Signer>>> {
+    let path = match value_of::<String>(arg_matches, name) {
+        Some(path) => path,
+        None => return Ok(None),
     };
+    signer_from_path(arg_matches, &path, name, wallet_manager)
+        .map(Some)
+        .map_err(|e| {
+            SignerError::SignerRead {
+                path,
+                reason: e.to_string(),
+            }
+            .into()
+        })
+}
 
-    Ok(())
+fn pubkey_of(arg_matches: &ArgMatches, name: &'static str) -> Result<Pubkey> {
+    let value = value_of::<String>(arg_matches, name).ok_or(InputError::InvalidArgument(name))?;
+    Pubkey::from_str(&value).map_err(|_| InputError::InvalidPubkey(value).into())
 }
@@ -0,0 +1,834 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use borsh::BorshSerialize;
+use futures::StreamExt;
+
+use solana_account_decoder::UiAccountEncoding;
+use solana_bridge::bridge_types::Vote;
+use solana_bridge::round_loader::{
+    RelayRound, RelayRoundProposal, RelayRoundProposalEventWithLen, Settings,
+};
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_program::bpf_loader_upgradeable;
+use solana_program::bpf_loader_upgradeable::UpgradeableLoaderState;
+use solana_program::message::Message;
+use solana_program::program_error::ProgramError;
+use solana_program::program_pack::Pack;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::client::{BufferData, DeployCost, DeployKeys, ProgramData, Receipt};
+use crate::error::{NetworkError, OnChainError, Result, SignerError};
+use crate::proposal::ProposalInput;
+use crate::rpc::Rpc;
+use crate::utils;
+
+/// Establishes a nonblocking RPC connection with the solana cluster
+/// configured by `solana config set --url <URL>`.
+pub fn establish_connection() -> Result<Arc<RpcClient>> {
+    let rpc_url = utils::get_rpc_url()?;
+    Ok(Arc::new(RpcClient::new_with_commitment(
+        rpc_url,
+        CommitmentConfig::confirmed(),
+    )))
+}
+
+/// Computes the total cost of deploying `program_len` bytes into a program
+/// with `max_data_len` capacity and checks it against the payer balance,
+/// before any transaction is sent.
+pub async fn check_deploy_cost<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    keys: &DeployKeys,
+    program_len: usize,
+    max_data_len: usize,
+    connection: &R,
+) -> Result<DeployCost> {
+    utils::print_header("Estimating deployment cost");
+
+    let programdata_rent = connection
+        .get_minimum_balance_for_rent_exemption(UpgradeableLoaderState::size_of_programdata(
+            max_data_len,
+        ))
+        .await?;
+    let program_rent = connection
+        .get_minimum_balance_for_rent_exemption(UpgradeableLoaderState::size_of_program())
+        .await?;
+
+    let blockhash = connection.get_latest_blockhash().await?;
+    let create_msg = |offset: u32, bytes: Vec<u8>| {
+        let instruction =
+            bpf_loader_upgradeable::write(&keys.buffer, &keys.buffer_authority, offset, bytes);
+        Message::new_with_blockhash(&[instruction], Some(&payer.pubkey()), &blockhash)
+    };
+    let write_fee = connection
+        .get_fee_for_message(&create_msg(0, Vec::new()))
+        .await?;
+
+    let chunk_size = utils::calculate_max_chunk_size(&create_msg);
+    let write_chunks = program_len.div_ceil(chunk_size);
+
+    // Same messages the deployment sends, so that a signer shared by several
+    // roles is only paid for once
+    let mut messages = vec![
+        bpf_loader_upgradeable::create_buffer(
+            &payer.pubkey(),
+            &keys.buffer,
+            &keys.buffer_authority,
+            programdata_rent,
+            max_data_len,
+        )?,
+        bpf_loader_upgradeable::deploy_with_max_program_len(
+            &payer.pubkey(),
+            &keys.program,
+            &keys.buffer,
+            &keys.upgrade_authority,
+            program_rent,
+            max_data_len,
+        )?,
+    ];
+    if keys.buffer_authority != keys.upgrade_authority {
+        messages.push(vec![bpf_loader_upgradeable::set_buffer_authority(
+            &keys.buffer,
+            &keys.buffer_authority,
+            &keys.upgrade_authority,
+        )]);
+    }
+    match keys.final_authority {
+        Some(authority) if authority != keys.upgrade_authority => {
+            messages.push(vec![bpf_loader_upgradeable::set_upgrade_authority(
+                &keys.program,
+                &keys.upgrade_authority,
+                Some(&authority),
+            )]);
+        }
+        _ => {}
+    }
+
+    let mut transaction_fees = 0;
+    for instructions in messages {
+        let message = Message::new_with_blockhash(&instructions, Some(&payer.pubkey()), &blockhash);
+        transaction_fees += connection.get_fee_for_message(&message).await?;
+    }
+
+    let cost = DeployCost {
+        programdata_rent,
+        program_rent,
+        write_chunks,
+        write_fees: write_fee * write_chunks as u64,
+        transaction_fees,
+    };
+    println!("{}", cost);
+
+    let balance = connection.get_balance(&payer.pubkey()).await?;
+    println!("Payer balance: {} lamports", balance);
+
+    if balance < cost.total() {
+        return Err(OnChainError::InsufficientFunds {
+            balance,
+            required: cost.total(),
+        }
+        .into());
+    }
+
+    Ok(cost)
+}
+
+pub async fn create_buffer<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    buffer: &Keypair,
+    authority_address: &Pubkey,
+    program_len: usize,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Creating buffer");
+
+    let minimum_balance = connection
+        .get_minimum_balance_for_rent_exemption(UpgradeableLoaderState::size_of_programdata(
+            program_len,
+        ))
+        .await?;
+
+    let mut transaction = Transaction::new_with_payer(
+        &bpf_loader_upgradeable::create_buffer(
+            &payer.pubkey(),
+            &buffer.pubkey(),
+            authority_address,
+            minimum_balance,
+            program_len,
+        )?,
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, buffer], connection.get_latest_blockhash().await?);
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    println!("Buffer: {}", buffer.pubkey());
+
+    Ok(receipt)
+}
+
+pub async fn write_buffer<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    buffer_authority: &Keypair,
+    buffer_pubkey: &Pubkey,
+    program_data: &[u8],
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Writing buffer");
+
+    let blockhash = connection.get_latest_blockhash().await?;
+
+    // Get messages
+    let create_msg = |offset: u32, bytes: Vec<u8>| {
+        let instruction =
+            bpf_loader_upgradeable::write(buffer_pubkey, &buffer_authority.pubkey(), offset, bytes);
+        Message::new_with_blockhash(&[instruction], Some(&payer.pubkey()), &blockhash)
+    };
+
+    let mut write_messages = vec![];
+    let chunk_size = utils::calculate_max_chunk_size(&create_msg);
+    for (chunk, i) in program_data.chunks(chunk_size).zip(0..) {
+        write_messages.push(create_msg((i * chunk_size) as u32, chunk.to_vec()));
+    }
+
+    // Send message
+    connection
+        .send_and_confirm_messages(&write_messages, &[payer, buffer_authority])
+        .await
+}
+
+pub async fn set_buffer_authority<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    current_authority: &Keypair,
+    buffer_address: &Pubkey,
+    new_authority_address: &Pubkey,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Setting buffer authority");
+
+    let mut transaction = Transaction::new_with_payer(
+        &[bpf_loader_upgradeable::set_buffer_authority(
+            buffer_address,
+            &current_authority.pubkey(),
+            new_authority_address,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[payer, current_authority],
+        connection.get_latest_blockhash().await?,
+    );
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    println!("Authority: {}", new_authority_address);
+
+    Ok(receipt)
+}
+
+/// Deploys the program from a buffer. The buffer authority becomes the
+/// upgrade authority of the program, it may be a hardware or remote signer.
+pub async fn deploy<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    program: &Keypair,
+    buffer_pubkey: &Pubkey,
+    upgrade_authority: &dyn Signer,
+    max_data_len: usize,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Deploying program");
+
+    let mut transaction = Transaction::new_with_payer(
+        &bpf_loader_upgradeable::deploy_with_max_program_len(
+            &payer.pubkey(),
+            &program.pubkey(),
+            buffer_pubkey,
+            &upgrade_authority.pubkey(),
+            connection
+                .get_minimum_balance_for_rent_exemption(UpgradeableLoaderState::size_of_program())
+                .await?,
+            max_data_len,
+        )?,
+        Some(&payer.pubkey()),
+    );
+    let signers: [&dyn Signer; 3] = [payer, program, upgrade_authority];
+    transaction
+        .try_sign(&signers, connection.get_latest_blockhash().await?)
+        .map_err(|e| SignerError::Sign(e.to_string()))?;
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    println!("Program: {}", program.pubkey());
+
+    Ok(receipt)
+}
+
+pub async fn set_program_authority<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    current_authority: &dyn Signer,
+    program_address: &Pubkey,
+    new_authority_address: &Pubkey,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Setting program authority");
+
+    let mut transaction = Transaction::new_with_payer(
+        &[bpf_loader_upgradeable::set_upgrade_authority(
+            program_address,
+            &current_authority.pubkey(),
+            Some(new_authority_address),
+        )],
+        Some(&payer.pubkey()),
+    );
+    let signers: [&dyn Signer; 2] = [payer, current_authority];
+    transaction
+        .try_sign(&signers, connection.get_latest_blockhash().await?)
+        .map_err(|e| SignerError::Sign(e.to_string()))?;
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    println!("Authority: {}", new_authority_address);
+
+    Ok(receipt)
+}
+
+pub async fn upgrade<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    authority: &Keypair,
+    program_address: &Pubkey,
+    buffer_address: &Pubkey,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Upgrading program");
+
+    let mut transaction = Transaction::new_with_payer(
+        &[bpf_loader_upgradeable::upgrade(
+            program_address,
+            buffer_address,
+            &authority.pubkey(),
+            &payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[payer, authority],
+        connection.get_latest_blockhash().await?,
+    );
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    println!("Program: {}", program_address);
+
+    Ok(receipt)
+}
+
+/// Grows the ProgramData account of a deployed program by `additional_bytes`,
+/// the payer funds the extra rent. No authority signature is needed.
+pub async fn extend_program<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    program_address: &Pubkey,
+    additional_bytes: u32,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Extending program");
+
+    let mut transaction = Transaction::new_with_payer(
+        &[bpf_loader_upgradeable::extend_program(
+            program_address,
+            Some(&payer.pubkey()),
+            additional_bytes,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer], connection.get_latest_blockhash().await?);
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    println!("Additional bytes: {}", additional_bytes);
+
+    Ok(receipt)
+}
+
+pub async fn create_relay_round_proposal<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    round_number: u32,
+    event_timestamp: u32,
+    event_transaction_lt: u64,
+    event_configuration: Pubkey,
+    proposal: &RelayRoundProposalEventWithLen,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Create Relay Round Proposal");
+
+    let mut transaction = Transaction::new_with_payer(
+        &[solana_bridge::round_loader::create_proposal_ix(
+            &payer.pubkey(),
+            &payer.pubkey(),
+            round_number,
+            event_timestamp,
+            event_transaction_lt,
+            event_configuration,
+            &proposal.data.try_to_vec()?,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer], connection.get_latest_blockhash().await?);
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    Ok(receipt)
+}
+
+pub async fn write_relay_round_proposal<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    proposal_pubkey: &Pubkey,
+    proposal: &RelayRoundProposalEventWithLen,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Writing Relay Round Proposal");
+
+    write_relay_round_proposal_chunks(payer, proposal_pubkey, proposal, &[], connection).await
+}
+
+/// Writes only the proposal chunks that differ from `written`, the proposal
+/// bytes already stored on-chain.
+async fn write_relay_round_proposal_chunks<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    proposal_pubkey: &Pubkey,
+    proposal: &RelayRoundProposalEventWithLen,
+    written: &[u8],
+    connection: &R,
+) -> Result<Receipt> {
+    let blockhash = connection.get_latest_blockhash().await?;
+
+    let create_msg = |offset: u32, bytes: Vec<u8>| {
+        let instruction =
+            solana_bridge::round_loader::write_proposal_ix(proposal_pubkey, offset, bytes);
+        Message::new_with_blockhash(&[instruction], Some(&payer.pubkey()), &blockhash)
+    };
+
+    let mut write_messages = vec![];
+    let chunk_size = utils::calculate_max_chunk_size(&create_msg);
+    for (chunk, i) in proposal.try_to_vec()?.chunks(chunk_size).zip(0..) {
+        let offset = i * chunk_size;
+        if written.get(offset..offset + chunk.len()) == Some(chunk) {
+            continue;
+        }
+        write_messages.push(create_msg(offset as u32, chunk.to_vec()));
+    }
+
+    if write_messages.is_empty() {
+        println!("Proposal is already written");
+        return Ok(Receipt::default());
+    }
+
+    // Send message
+    connection
+        .send_and_confirm_messages(&write_messages, &[payer])
+        .await
+}
+
+pub async fn finalize_relay_round_proposal<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    proposal_pubkey: &Pubkey,
+    round_number: u32,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Finalize Relay Round Proposal");
+
+    let mut transaction = Transaction::new_with_payer(
+        &[solana_bridge::round_loader::finalize_proposal_ix(
+            &payer.pubkey(),
+            proposal_pubkey,
+            round_number,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer], connection.get_latest_blockhash().await?);
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    Ok(receipt)
+}
+
+/// Creates, writes and finalizes a relay round proposal, picking up where a
+/// previous attempt stopped. An existing proposal account is reused, only the
+/// chunks that differ from the on-chain data are rewritten, and a proposal
+/// that is already finalized is left as is.
+pub async fn propose_relay_round<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    round_number: u32,
+    input: &ProposalInput,
+    connection: &R,
+) -> Result<Receipt> {
+    let current_round = get_relay_round(round_number, connection).await?;
+    input.validate_next_round(&current_round)?;
+
+    let proposal = input.proposal();
+    let proposal_pubkey = input.address(round_number)?;
+
+    let mut receipt = Receipt::default();
+
+    let written = match connection.get_account(&proposal_pubkey).await? {
+        None => {
+            receipt.merge(
+                create_relay_round_proposal(
+                    payer,
+                    round_number,
+                    input.event_timestamp,
+                    input.event_transaction_lt,
+                    input.event_configuration,
+                    &proposal,
+                    connection,
+                )
+                .await?,
+            );
+            Vec::new()
+        }
+        Some(account) => {
+            if account.owner != solana_bridge::round_loader::id() {
+                return Err(
+                    OnChainError::InvalidAccountOwner(proposal_pubkey, account.owner).into(),
+                );
+            }
+            println!("Proposal account already exists");
+
+            // Chunks that are not written yet read as zeroes, an account that
+            // does not decode at all is not a proposal to resume
+            match RelayRoundProposal::unpack_from_slice(&account.data) {
+                Ok(state) => {
+                    let meta = &state.meta.data;
+                    if state.author != payer.pubkey()
+                        || meta.event_timestamp != input.event_timestamp
+                        || meta.event_transaction_lt != input.event_transaction_lt
+                        || meta.event_configuration != input.event_configuration
+                    {
+                        return Err(OnChainError::InvalidProposalState(format!(
+                            "existing proposal ({}) was created with different parameters",
+                            proposal_pubkey
+                        ))
+                        .into());
+                    }
+
+                    if state.is_initialized {
+                        println!("Proposal is already finalized");
+                        return Ok(receipt);
+                    }
+
+                    state.event.try_to_vec()?
+                }
+                Err(e) => return Err(OnChainError::AccountDecode(proposal_pubkey, e).into()),
+            }
+        }
+    };
+
+    utils::print_header("Writing Relay Round Proposal");
+    receipt.merge(
+        write_relay_round_proposal_chunks(payer, &proposal_pubkey, &proposal, &written, connection)
+            .await?,
+    );
+
+    receipt.merge(
+        finalize_relay_round_proposal(payer, &proposal_pubkey, round_number, connection).await?,
+    );
+
+    Ok(receipt)
+}
+
+/// Votes for a finalized proposal with `voter`, which must be one of the
+/// relays of the round the proposal was created in.
+pub async fn vote_for_relay_round_proposal<R: Rpc + ?Sized>(
+    voter: &Keypair,
+    proposal_pubkey: &Pubkey,
+    vote: Vote,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Vote for Relay Round Proposal");
+
+    let proposal = get_relay_round_proposal(proposal_pubkey, connection).await?;
+    if !proposal.is_initialized {
+        return Err(
+            OnChainError::InvalidProposalState("proposal is not finalized".to_string()).into(),
+        );
+    }
+    if proposal.is_executed {
+        return Err(
+            OnChainError::InvalidProposalState("proposal is already executed".to_string()).into(),
+        );
+    }
+
+    let relay_round = get_relay_round(proposal.round_number, connection).await?;
+    if !relay_round.relays.contains(&voter.pubkey()) {
+        return Err(OnChainError::NotARelay(voter.pubkey(), proposal.round_number).into());
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[solana_bridge::round_loader::vote_for_proposal_ix(
+            &voter.pubkey(),
+            proposal_pubkey,
+            proposal.round_number,
+            vote,
+        )],
+        Some(&voter.pubkey()),
+    );
+    transaction.sign(&[voter], connection.get_latest_blockhash().await?);
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    Ok(receipt)
+}
+
+/// Executes a proposal that collected enough votes, creating the relay round
+/// it proposes.
+pub async fn execute_relay_round_proposal<R: Rpc + ?Sized>(
+    payer: &Keypair,
+    proposal_pubkey: &Pubkey,
+    connection: &R,
+) -> Result<Receipt> {
+    utils::print_header("Execute Relay Round Proposal");
+
+    let proposal = get_relay_round_proposal(proposal_pubkey, connection).await?;
+    if proposal.is_executed {
+        return Err(
+            OnChainError::InvalidProposalState("proposal is already executed".to_string()).into(),
+        );
+    }
+
+    let confirmed = utils::confirmed_votes(&proposal);
+    if confirmed < proposal.required_votes {
+        return Err(OnChainError::NotEnoughVotes {
+            confirmed,
+            required: proposal.required_votes,
+        }
+        .into());
+    }
+
+    let round_number = proposal.event.data.round_num;
+    let relay_round_pubkey = solana_bridge::round_loader::get_relay_round_address(round_number);
+    println!("Relay Round address: {}", relay_round_pubkey);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[solana_bridge::round_loader::execute_proposal_ix(
+            &payer.pubkey(),
+            proposal_pubkey,
+            round_number,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer], connection.get_latest_blockhash().await?);
+
+    let receipt = connection
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+
+    Ok(receipt)
+}
+
+/// Follows a proposal over the websocket at `websocket_url`, printing votes
+/// as they arrive, until it is executed. Fails with `ProposalExpired` once its
+/// relay round ends first.
+pub async fn watch_relay_round_proposal<R: Rpc + ?Sized>(
+    proposal_pubkey: &Pubkey,
+    websocket_url: &str,
+    connection: &R,
+) -> Result<()> {
+    // Subscribe before reading the proposal, a vote landing in between would
+    // otherwise go unnoticed
+    let pubsub_client = PubsubClient::new(websocket_url).await?;
+    let (mut updates, unsubscribe) = pubsub_client
+        .account_subscribe(
+            proposal_pubkey,
+            Some(RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..Default::default()
+            }),
+        )
+        .await?;
+
+    let outcome = async {
+        let proposal = get_relay_round_proposal(proposal_pubkey, connection).await?;
+        let relay_round = get_relay_round(proposal.round_number, connection).await?;
+
+        utils::print_votes(&proposal, Some(&relay_round));
+        if proposal.is_executed {
+            return Ok(());
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let deadline = tokio::time::sleep(Duration::from_secs(
+            (relay_round.round_end as u64).saturating_sub(now),
+        ));
+        tokio::pin!(deadline);
+
+        let round_number = proposal.round_number;
+        let mut signers = proposal.signers;
+        loop {
+            tokio::select! {
+                _ = &mut deadline => {
+                    break Err(OnChainError::ProposalExpired(*proposal_pubkey, round_number).into())
+                }
+                update = updates.next() => {
+                    let account: Account = match update {
+                        Some(update) => match update.value.decode() {
+                            Some(account) => account,
+                            None => continue,
+                        },
+                        None => break Err(NetworkError::SubscriptionClosed(*proposal_pubkey).into()),
+                    };
+
+                    let proposal = match RelayRoundProposal::unpack_from_slice(&account.data) {
+                        Ok(proposal) => proposal,
+                        Err(e) => break Err(OnChainError::AccountDecode(*proposal_pubkey, e).into()),
+                    };
+
+                    if proposal.signers != signers {
+                        println!();
+                        utils::print_votes(&proposal, Some(&relay_round));
+                        signers = proposal.signers;
+                    }
+                    if proposal.is_executed {
+                        break Ok(());
+                    }
+                }
+            }
+        }
+    }
+    .await;
+
+    unsubscribe().await;
+
+    outcome
+}
+
+/// Loads an upgradeable program and its ProgramData account, `None` if the
+/// program does not exist.
+pub async fn get_program_data<R: Rpc + ?Sized>(
+    program_address: &Pubkey,
+    connection: &R,
+) -> Result<Option<ProgramData>> {
+    let program_account = match connection.get_account(program_address).await? {
+        Some(account) => account,
+        None => return Ok(None),
+    };
+    let address = match get_loader_state(program_address, &program_account)? {
+        UpgradeableLoaderState::Program {
+            programdata_address,
+        } => programdata_address,
+        _ => {
+            return Err(OnChainError::AccountDecode(
+                *program_address,
+                ProgramError::InvalidAccountData,
+            )
+            .into())
+        }
+    };
+
+    let programdata_account = connection
+        .get_account(&address)
+        .await?
+        .ok_or(OnChainError::AccountNotFound(address))?;
+    match get_loader_state(&address, &programdata_account)? {
+        UpgradeableLoaderState::ProgramData {
+            slot,
+            upgrade_authority_address,
+        } => Ok(Some(ProgramData {
+            address,
+            slot,
+            upgrade_authority: upgrade_authority_address,
+            data: programdata_account.data
+                [UpgradeableLoaderState::size_of_programdata_metadata()..]
+                .to_vec(),
+        })),
+        _ => Err(OnChainError::AccountDecode(address, ProgramError::InvalidAccountData).into()),
+    }
+}
+
+/// Loads a program buffer account.
+pub async fn get_buffer<R: Rpc + ?Sized>(
+    buffer_address: &Pubkey,
+    connection: &R,
+) -> Result<BufferData> {
+    let account = connection
+        .get_account(buffer_address)
+        .await?
+        .ok_or(OnChainError::AccountNotFound(*buffer_address))?;
+    match get_loader_state(buffer_address, &account)? {
+        UpgradeableLoaderState::Buffer { authority_address } => Ok(BufferData {
+            authority: authority_address,
+            data: account.data[UpgradeableLoaderState::size_of_buffer_metadata()..].to_vec(),
+        }),
+        _ => Err(
+            OnChainError::AccountDecode(*buffer_address, ProgramError::InvalidAccountData).into(),
+        ),
+    }
+}
+
+pub async fn get_relay_round_proposal<R: Rpc + ?Sized>(
+    proposal_pubkey: &Pubkey,
+    connection: &R,
+) -> Result<RelayRoundProposal> {
+    get_state(proposal_pubkey, connection).await
+}
+
+pub async fn get_round_loader_settings<R: Rpc + ?Sized>(connection: &R) -> Result<Settings> {
+    let settings_pubkey = solana_bridge::round_loader::get_settings_address();
+    get_state(&settings_pubkey, connection).await
+}
+
+pub async fn get_relay_round<R: Rpc + ?Sized>(
+    round_number: u32,
+    connection: &R,
+) -> Result<RelayRound> {
+    let relay_round_pubkey = solana_bridge::round_loader::get_relay_round_address(round_number);
+    get_state(&relay_round_pubkey, connection).await
+}
+
+async fn get_state<T: Pack, R: Rpc + ?Sized>(address: &Pubkey, connection: &R) -> Result<T> {
+    let account = connection
+        .get_account(address)
+        .await?
+        .ok_or(OnChainError::AccountNotFound(*address))?;
+
+    if account.owner != solana_bridge::round_loader::id() {
+        return Err(OnChainError::InvalidAccountOwner(*address, account.owner).into());
+    }
+
+    T::unpack_from_slice(&account.data).map_err(|e| OnChainError::AccountDecode(*address, e).into())
+}
+
+fn get_loader_state(address: &Pubkey, account: &Account) -> Result<UpgradeableLoaderState> {
+    if account.owner != bpf_loader_upgradeable::id() {
+        return Err(OnChainError::InvalidAccountOwner(*address, account.owner).into());
+    }
+
+    bincode::deserialize(&account.data)
+        .map_err(|_| OnChainError::AccountDecode(*address, ProgramError::InvalidAccountData).into())
+}
@@ -0,0 +1,280 @@
+use solana_bridge::error::SolanaBridgeError;
+use solana_bridge::round_loader;
+
+use solana_program::bpf_loader_upgradeable;
+use solana_program::decode_error::DecodeError;
+use solana_program::instruction::InstructionError;
+use solana_program::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+
+use crate::error::OnChainError;
+
+/// Turns a failed transaction into an error naming the failed instruction and
+/// its program, with an explanation for the programs this CLI talks to.
+pub fn decode_transaction_error(error: TransactionError, message: &Message) -> OnChainError {
+    let (index, instruction_error) = match &error {
+        TransactionError::InstructionError(index, instruction_error) => (*index, instruction_error),
+        _ => return OnChainError::Transaction(error),
+    };
+
+    let program_id = match message
+        .instructions
+        .get(index as usize)
+        .and_then(|instruction| {
+            message
+                .account_keys
+                .get(instruction.program_id_index as usize)
+        }) {
+        Some(program_id) => *program_id,
+        None => return OnChainError::Transaction(error),
+    };
+
+    let reason = match explain(&program_id, instruction_error) {
+        Some(explanation) => format!("{} ({})", explanation, instruction_error),
+        None => instruction_error.to_string(),
+    };
+
+    OnChainError::ProgramFailed {
+        index,
+        program_id,
+        reason,
+    }
+}
+
+/// Explains an instruction error returned by the round loader or the
+/// upgradeable BPF loader.
+pub fn explain(program_id: &Pubkey, error: &InstructionError) -> Option<String> {
+    if *program_id == round_loader::id() {
+        explain_round_loader(error)
+    } else if *program_id == bpf_loader_upgradeable::id() {
+        explain_bpf_loader_upgradeable(error).map(str::to_string)
+    } else {
+        None
+    }
+}
+
+/// Only the round loader's own errors are explained, builtin instruction
+/// errors are returned by it for more than one reason.
+fn explain_round_loader(error: &InstructionError) -> Option<String> {
+    match error {
+        InstructionError::Custom(code) => {
+            <SolanaBridgeError as DecodeError<SolanaBridgeError>>::decode_custom_error_to_enum(
+                *code,
+            )
+            .map(|error| error.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn explain_bpf_loader_upgradeable(error: &InstructionError) -> Option<&'static str> {
+    let explanation = match error {
+        InstructionError::IncorrectAuthority => {
+            "signer is not the authority of the buffer or the program"
+        }
+        InstructionError::Immutable => "program or buffer has no authority and can not be changed",
+        InstructionError::MissingRequiredSignature => "authority did not sign the instruction",
+        InstructionError::AccountDataTooSmall => {
+            "program data account is too small for the new program, extend it first"
+        }
+        InstructionError::AccountAlreadyInitialized => "program account is already deployed",
+        InstructionError::InsufficientFunds => {
+            "payer can not cover rent for the program data account"
+        }
+        InstructionError::InvalidAccountData => {
+            "buffer, program or program data account does not match, or the ELF failed verification"
+        }
+        InstructionError::InvalidArgument => "invalid program data address or max data length",
+        InstructionError::AccountNotExecutable => "program account is not executable",
+        _ => return None,
+    };
+
+    Some(explanation)
+}
+
+/// Program log lines worth showing to an operator: messages logged by the
+/// programs themselves and the reasons they failed.
+pub fn relevant_logs(logs: &[String]) -> impl Iterator<Item = &str> {
+    logs.iter().map(String::as_str).filter(|log| {
+        log.starts_with("Program log: ")
+            || (log.starts_with("Program ") && log.contains(" failed: "))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::instruction::{AccountMeta, Instruction};
+    use solana_program::system_instruction;
+
+    use super::*;
+
+    fn message(program_id: Pubkey) -> Message {
+        let payer = Pubkey::new_unique();
+        Message::new(
+            &[
+                system_instruction::transfer(&payer, &Pubkey::new_unique(), 1),
+                Instruction::new_with_bytes(
+                    program_id,
+                    &[],
+                    vec![AccountMeta::new(Pubkey::new_unique(), false)],
+                ),
+            ],
+            Some(&payer),
+        )
+    }
+
+    #[test]
+    fn decode_round_loader_error() {
+        let (code, expected) = (0..256)
+            .find_map(|code| {
+                <SolanaBridgeError as DecodeError<SolanaBridgeError>>::decode_custom_error_to_enum(
+                    code,
+                )
+                .map(|error| (code, error.to_string()))
+            })
+            .unwrap();
+
+        let error = decode_transaction_error(
+            TransactionError::InstructionError(1, InstructionError::Custom(code)),
+            &message(round_loader::id()),
+        );
+        match error {
+            OnChainError::ProgramFailed {
+                index,
+                program_id,
+                reason,
+            } => {
+                assert_eq!(index, 1);
+                assert_eq!(program_id, round_loader::id());
+                assert_eq!(
+                    reason,
+                    format!("{} ({})", expected, InstructionError::Custom(code))
+                );
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn decode_bpf_loader_upgradeable_error() {
+        let error = decode_transaction_error(
+            TransactionError::InstructionError(1, InstructionError::IncorrectAuthority),
+            &message(bpf_loader_upgradeable::id()),
+        );
+        match error {
+            OnChainError::ProgramFailed { reason, .. } => assert_eq!(
+                reason,
+                format!(
+                    "signer is not the authority of the buffer or the program ({})",
+                    InstructionError::IncorrectAuthority
+                )
+            ),
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn decode_unexplained_error() {
+        let program_id = Pubkey::new_unique();
+        let error = decode_transaction_error(
+            TransactionError::InstructionError(1, InstructionError::Custom(1)),
+            &message(program_id),
+        );
+        match error {
+            OnChainError::ProgramFailed {
+                program_id: failed,
+                reason,
+                ..
+            } => {
+                assert_eq!(failed, program_id);
+                assert_eq!(reason, InstructionError::Custom(1).to_string());
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+
+        let error = decode_transaction_error(
+            TransactionError::InstructionError(1, InstructionError::MissingRequiredSignature),
+            &message(round_loader::id()),
+        );
+        match error {
+            OnChainError::ProgramFailed { reason, .. } => assert_eq!(
+                reason,
+                InstructionError::MissingRequiredSignature.to_string()
+            ),
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn keep_errors_without_instruction() {
+        let message = message(round_loader::id());
+
+        assert!(matches!(
+            decode_transaction_error(TransactionError::AccountInUse, &message),
+            OnChainError::Transaction(TransactionError::AccountInUse)
+        ));
+        assert!(matches!(
+            decode_transaction_error(
+                TransactionError::InstructionError(2, InstructionError::Custom(0)),
+                &message
+            ),
+            OnChainError::Transaction(TransactionError::InstructionError(2, _))
+        ));
+    }
+
+    #[test]
+    fn explain_only_known_programs() {
+        assert_eq!(
+            explain(
+                &bpf_loader_upgradeable::id(),
+                &InstructionError::AccountDataTooSmall
+            ),
+            Some(
+                "program data account is too small for the new program, extend it first"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            explain(
+                &bpf_loader_upgradeable::id(),
+                &InstructionError::ComputationalBudgetExceeded
+            ),
+            None
+        );
+        assert_eq!(
+            explain(
+                &round_loader::id(),
+                &InstructionError::AccountAlreadyInitialized
+            ),
+            None
+        );
+        assert_eq!(
+            explain(&Pubkey::new_unique(), &InstructionError::IncorrectAuthority),
+            None
+        );
+    }
+
+    #[test]
+    fn filter_relevant_logs() {
+        let logs = [
+            "Program 11111111111111111111111111111111 invoke [1]",
+            "Program log: Instruction: Vote",
+            "Program log: Error: not a relay",
+            "Program 11111111111111111111111111111111 consumed 2000 of 200000 compute units",
+            "Program 11111111111111111111111111111111 failed: custom program error: 0x4",
+            "Program data: AQID",
+        ]
+        .map(str::to_string);
+
+        assert_eq!(
+            relevant_logs(&logs).collect::<Vec<_>>(),
+            vec![
+                "Program log: Instruction: Vote",
+                "Program log: Error: not a relay",
+                "Program 11111111111111111111111111111111 failed: custom program error: 0x4",
+            ]
+        );
+        assert_eq!(relevant_logs(&[]).count(), 0);
+    }
+}
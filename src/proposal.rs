@@ -0,0 +1,462 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use borsh::BorshSerialize;
+use yaml_rust::Yaml;
+
+use solana_bridge::round_loader::{
+    get_proposal_address, RelayRound, RelayRoundProposalEventWithLen, MAX_RELAYS, MIN_RELAYS,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::error::{Error, InputError, Result};
+use crate::everscale::EverAddress;
+use crate::utils;
+
+/// Relay round proposal parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProposalInput {
+    pub event_timestamp: u32,
+    pub event_transaction_lt: u64,
+    pub event_configuration: Pubkey,
+    pub round_number: u32,
+    pub round_end: u32,
+    pub relays: Vec<Pubkey>,
+}
+
+impl ProposalInput {
+    /// Reads proposal parameters from a JSON or YAML file:
+    ///
+    /// ```yaml
+    /// event_timestamp: 1650000000
+    /// transaction_lt: 27000000000001
+    /// configuration: "0:4dbd..."
+    /// round_number: 5
+    /// round_end: 1651000000
+    /// relays:
+    ///   - "0:2b3f..."
+    ///   - "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"
+    /// ```
+    pub fn from_file(path: &str) -> Result<Self> {
+        let content =
+            std::fs::read_to_string(path).map_err(|source| InputError::ProposalFileRead {
+                path: path.to_string(),
+                source,
+            })?;
+        Self::from_str(&content)
+    }
+
+    pub fn proposal(&self) -> RelayRoundProposalEventWithLen {
+        RelayRoundProposalEventWithLen::new(self.round_number, self.relays.clone(), self.round_end)
+    }
+
+    /// Address of the proposal created during relay round `round_number`.
+    pub fn address(&self, round_number: u32) -> Result<Pubkey> {
+        Ok(get_proposal_address(
+            round_number,
+            self.event_timestamp,
+            self.event_transaction_lt,
+            &self.event_configuration,
+            &self.proposal().data.try_to_vec()?,
+        ))
+    }
+
+    /// Checks the proposal on its own: relay count bounds, duplicate and
+    /// zero relays.
+    pub fn validate(&self) -> Result<()> {
+        if self.relays.len() < MIN_RELAYS as usize || self.relays.len() > MAX_RELAYS as usize {
+            return Err(InputError::InvalidProposal(format!(
+                "expected from ({}) to ({}) relays, got ({})",
+                MIN_RELAYS,
+                MAX_RELAYS,
+                self.relays.len()
+            ))
+            .into());
+        }
+
+        let mut relays = HashSet::with_capacity(self.relays.len());
+        for (i, relay) in self.relays.iter().enumerate() {
+            if *relay == Pubkey::default() {
+                return Err(
+                    InputError::InvalidProposal(format!("relay #{} is a zero key", i)).into(),
+                );
+            }
+            if !relays.insert(relay) {
+                return Err(InputError::InvalidProposal(format!(
+                    "relay #{} ({}) is duplicated",
+                    i, relay
+                ))
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the proposal follows `current_round`: it must propose the
+    /// next round number and end after the current round does.
+    pub fn validate_next_round(&self, current_round: &RelayRound) -> Result<()> {
+        self.validate()?;
+
+        if Some(self.round_number) != current_round.round_number.checked_add(1) {
+            return Err(InputError::InvalidProposal(format!(
+                "proposal round number ({}) does not follow current round ({})",
+                self.round_number, current_round.round_number
+            ))
+            .into());
+        }
+
+        if self.round_end <= current_round.round_end {
+            return Err(InputError::InvalidProposal(format!(
+                "proposal round end ({}) is not after current round end ({})",
+                self.round_end, current_round.round_end
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for ProposalInput {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // JSON is a subset of YAML, so a single loader handles both formats
+        let document = utils::load_yaml(s).map_err(InputError::InvalidProposal)?;
+
+        let relays = match &document["relays"] {
+            Yaml::Array(relays) => relays
+                .iter()
+                .enumerate()
+                .map(|(i, relay)| match relay.as_str() {
+                    Some(relay) => parse_pubkey(relay).map_err(|_| {
+                        InputError::InvalidProposal(format!("invalid relay #{}: ({})", i, relay))
+                            .into()
+                    }),
+                    None => Err(InputError::InvalidProposal(format!(
+                        "relay #{} must be a string",
+                        i
+                    ))
+                    .into()),
+                })
+                .collect::<Result<Vec<_>>>()?,
+            Yaml::BadValue => {
+                return Err(
+                    InputError::InvalidProposal("missing `relays` field".to_string()).into(),
+                )
+            }
+            _ => {
+                return Err(
+                    InputError::InvalidProposal("`relays` must be a list".to_string()).into(),
+                )
+            }
+        };
+
+        let event_configuration = match document["configuration"].as_str() {
+            Some(configuration) => configuration.parse::<EverAddress>()?.to_pubkey()?,
+            None => {
+                return Err(InputError::InvalidProposal(
+                    "missing `configuration` field".to_string(),
+                )
+                .into())
+            }
+        };
+
+        let input = Self {
+            event_timestamp: get_number(&document, "event_timestamp")?,
+            event_transaction_lt: get_number(&document, "transaction_lt")?,
+            event_configuration,
+            round_number: get_number(&document, "round_number")?,
+            round_end: get_number(&document, "round_end")?,
+            relays,
+        };
+        input.validate()?;
+
+        Ok(input)
+    }
+}
+
+/// Parses a pubkey given as base58, as hex, or as an Everscale-style
+/// `0:<hex>` address.
+pub fn parse_pubkey(s: &str) -> Result<Pubkey> {
+    let s = s.trim();
+
+    if s.contains(':') || s.len() == 64 {
+        return s.parse::<EverAddress>()?.to_pubkey();
+    }
+
+    Pubkey::from_str(s).map_err(|_| InputError::InvalidPubkey(s.to_string()).into())
+}
+
+fn get_number<T>(document: &Yaml, field: &str) -> Result<T>
+where
+    T: TryFrom<i64> + FromStr,
+{
+    let value = match &document[field] {
+        Yaml::Integer(value) => T::try_from(*value).ok(),
+        // Large values such as logical times may be quoted
+        Yaml::String(value) => value.parse().ok(),
+        Yaml::BadValue => {
+            return Err(InputError::InvalidProposal(format!("missing `{}` field", field)).into());
+        }
+        _ => None,
+    };
+
+    value.ok_or_else(|| InputError::InvalidProposal(format!("invalid `{}` field", field)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_bridge::round_loader::AccountKind;
+
+    use super::*;
+
+    const ROUND_NUMBER: u32 = 5;
+    const ROUND_END: u32 = 1_650_000_000;
+
+    fn input() -> ProposalInput {
+        ProposalInput {
+            event_timestamp: 1,
+            event_transaction_lt: 2,
+            event_configuration: Pubkey::new_unique(),
+            round_number: ROUND_NUMBER + 1,
+            round_end: ROUND_END + 1,
+            relays: (0..MIN_RELAYS.max(2))
+                .map(|_| Pubkey::new_unique())
+                .collect(),
+        }
+    }
+
+    fn current_round() -> RelayRound {
+        RelayRound {
+            is_initialized: true,
+            account_kind: AccountKind::RelayRound(255),
+            round_number: ROUND_NUMBER,
+            round_end: ROUND_END,
+            relays: vec![Pubkey::new_unique()],
+        }
+    }
+
+    fn assert_invalid<T: std::fmt::Debug>(result: Result<T>, expected: &str) {
+        match result {
+            Err(Error::Input(InputError::InvalidProposal(reason))) => assert!(
+                reason.contains(expected),
+                "`{}` does not mention `{}`",
+                reason,
+                expected
+            ),
+            result => panic!("expected an invalid proposal, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn validate_proposal() {
+        input().validate().unwrap();
+        input().validate_next_round(&current_round()).unwrap();
+    }
+
+    #[test]
+    fn reject_relay_count_out_of_bounds() {
+        let mut proposal = input();
+        proposal.relays.truncate(MIN_RELAYS as usize - 1);
+        assert_invalid(proposal.validate(), "relays, got");
+
+        proposal.relays = (0..=MAX_RELAYS).map(|_| Pubkey::new_unique()).collect();
+        assert_invalid(proposal.validate(), "relays, got");
+    }
+
+    #[test]
+    fn reject_zero_relay() {
+        let mut proposal = input();
+        proposal.relays[1] = Pubkey::default();
+        assert_invalid(proposal.validate(), "relay #1 is a zero key");
+    }
+
+    #[test]
+    fn reject_duplicate_relay() {
+        let mut proposal = input();
+        proposal.relays[1] = proposal.relays[0];
+        assert_invalid(proposal.validate(), "relay #1");
+        assert_invalid(proposal.validate(), "is duplicated");
+    }
+
+    #[test]
+    fn reject_round_number_not_following_current() {
+        for round_number in [ROUND_NUMBER, ROUND_NUMBER + 2] {
+            let proposal = ProposalInput {
+                round_number,
+                ..input()
+            };
+            assert_invalid(
+                proposal.validate_next_round(&current_round()),
+                "does not follow current round",
+            );
+        }
+
+        let current_round = RelayRound {
+            round_number: u32::MAX,
+            ..current_round()
+        };
+        let proposal = ProposalInput {
+            round_number: 0,
+            ..input()
+        };
+        assert_invalid(
+            proposal.validate_next_round(&current_round),
+            "does not follow current round",
+        );
+    }
+
+    #[test]
+    fn reject_round_end_not_after_current() {
+        for round_end in [ROUND_END - 1, ROUND_END] {
+            let proposal = ProposalInput {
+                round_end,
+                ..input()
+            };
+            assert_invalid(
+                proposal.validate_next_round(&current_round()),
+                "is not after current round end",
+            );
+        }
+    }
+
+    #[test]
+    fn validate_next_round_checks_relays() {
+        let mut proposal = input();
+        proposal.relays[1] = Pubkey::default();
+        assert_invalid(proposal.validate_next_round(&current_round()), "zero key");
+    }
+
+    /// Proposal file with `relays` and `extra` fields spliced in.
+    fn proposal_file(configuration: &[u8; 32], relays: &str, extra: &str) -> String {
+        format!(
+            "event_timestamp: 1650000000\n\
+             configuration: \"0:{}\"\n\
+             round_number: 6\n\
+             round_end: 1651000000\n\
+             relays: {}\n\
+             {}",
+            hex::encode(configuration),
+            relays,
+            extra
+        )
+    }
+
+    fn relays(count: usize) -> Vec<Pubkey> {
+        (0..count).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    #[test]
+    fn parse_json() {
+        let configuration = Pubkey::new_unique();
+        let relays = relays(MIN_RELAYS as usize);
+        let content = format!(
+            r#"{{
+                "event_timestamp": 1650000000,
+                "transaction_lt": 27000000000001,
+                "configuration": "0:{}",
+                "round_number": 6,
+                "round_end": 1651000000,
+                "relays": [{}]
+            }}"#,
+            hex::encode(configuration.to_bytes()),
+            relays
+                .iter()
+                .map(|relay| format!("\"{}\"", relay))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        assert_eq!(
+            content.parse::<ProposalInput>().unwrap(),
+            ProposalInput {
+                event_timestamp: 1_650_000_000,
+                event_transaction_lt: 27_000_000_000_001,
+                event_configuration: configuration,
+                round_number: 6,
+                round_end: 1_651_000_000,
+                relays,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_yaml_relay_formats() {
+        let configuration = Pubkey::new_unique();
+        let relays = relays(MIN_RELAYS.max(3) as usize);
+        let formats = relays
+            .iter()
+            .enumerate()
+            .map(|(i, relay)| match i % 3 {
+                0 => format!("\n  - \"{}\"", relay),
+                1 => format!("\n  - \"{}\"", hex::encode(relay.to_bytes())),
+                _ => format!("\n  - \"0:{}\"", hex::encode(relay.to_bytes())),
+            })
+            .collect::<String>();
+        let content = proposal_file(
+            &configuration.to_bytes(),
+            &formats,
+            "transaction_lt: \"18446744073709551615\"\n",
+        );
+
+        let input = content.parse::<ProposalInput>().unwrap();
+        assert_eq!(input.event_configuration, configuration);
+        assert_eq!(input.event_transaction_lt, u64::MAX);
+        assert_eq!(input.relays, relays);
+    }
+
+    #[test]
+    fn reject_missing_fields() {
+        let configuration = Pubkey::new_unique().to_bytes();
+        let relays = relays(MIN_RELAYS as usize)
+            .iter()
+            .map(|relay| format!("\n  - \"{}\"", relay))
+            .collect::<String>();
+
+        assert_invalid(
+            proposal_file(&configuration, &relays, "").parse::<ProposalInput>(),
+            "missing `transaction_lt` field",
+        );
+        assert_invalid(
+            "configuration: \"0:00\"\n".parse::<ProposalInput>(),
+            "missing `relays` field",
+        );
+
+        let content = proposal_file(&configuration, &relays, "transaction_lt: 1\n")
+            .replace("round_end: 1651000000\n", "");
+        assert_invalid(
+            content.parse::<ProposalInput>(),
+            "missing `round_end` field",
+        );
+    }
+
+    #[test]
+    fn reject_invalid_fields() {
+        let configuration = Pubkey::new_unique().to_bytes();
+
+        assert_invalid(
+            proposal_file(&configuration, "relay", "transaction_lt: 1\n").parse::<ProposalInput>(),
+            "`relays` must be a list",
+        );
+        assert_invalid(
+            proposal_file(&configuration, "[1]", "transaction_lt: 1\n").parse::<ProposalInput>(),
+            "relay #0 must be a string",
+        );
+        assert_invalid(
+            proposal_file(&configuration, "[\"relay\"]", "transaction_lt: 1\n")
+                .parse::<ProposalInput>(),
+            "invalid relay #0",
+        );
+
+        let relays = relays(MIN_RELAYS as usize)
+            .iter()
+            .map(|relay| format!("\n  - \"{}\"", relay))
+            .collect::<String>();
+        assert_invalid(
+            proposal_file(&configuration, &relays, "transaction_lt: -1\n").parse::<ProposalInput>(),
+            "invalid `transaction_lt` field",
+        );
+    }
+}
@@ -0,0 +1,760 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use yaml_rust::yaml::Hash as YamlHash;
+use yaml_rust::{Yaml, YamlEmitter};
+
+use solana_sdk::clock::Slot;
+use solana_sdk::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature, Signer};
+
+use crate::client::Receipt;
+use crate::deploy::DeployPlan;
+use crate::error::{InputError, OnChainError, Result, SignerError};
+use crate::nonblocking::client::{
+    create_buffer, extend_program, get_buffer, get_program_data, set_buffer_authority,
+    set_program_authority, upgrade, write_buffer,
+};
+use crate::rpc::Rpc;
+use crate::utils;
+
+/// Programs released together, in release order.
+///
+/// ```yaml
+/// programs:
+///   - name: round_loader
+///     elf: target/deploy/round_loader.so
+///     program_keypair: keys/round_loader.json
+///     authority: "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"
+///     max_len: 400000
+///   - name: token_proxy
+///     elf: target/deploy/token_proxy.so
+///     program_id: "DqqsutZd6XqXoGMaUAXBVWRh4uLpYYb2DnPrzaJCZCCW"
+///     authority: "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"
+/// ```
+///
+/// Paths are relative to the manifest. A program keypair is only required to
+/// deploy a program that does not exist yet. Existing programs smaller than
+/// `max_len`, or than the new ELF, are extended before the upgrade.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub programs: Vec<ManifestProgram>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestProgram {
+    pub name: String,
+    pub elf: PathBuf,
+    pub program_id: Pubkey,
+    pub program_keypair: Option<PathBuf>,
+    /// Upgrade authority once released
+    pub authority: Pubkey,
+    pub max_len: Option<usize>,
+}
+
+impl Manifest {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|source| InputError::ManifestRead {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let document = utils::load_yaml(&content).map_err(InputError::InvalidManifest)?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let programs = match &document["programs"] {
+            Yaml::Array(programs) => programs,
+            _ => {
+                return Err(
+                    InputError::InvalidManifest("`programs` must be a list".to_string()).into(),
+                )
+            }
+        };
+
+        let programs = programs
+            .iter()
+            .enumerate()
+            .map(|(i, program)| parse_manifest_program(program, base, i))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { programs })
+    }
+}
+
+fn parse_manifest_program(program: &Yaml, base: &Path, i: usize) -> Result<ManifestProgram> {
+    let field = |name: &str| {
+        program[name].as_str().ok_or_else(|| {
+            InputError::InvalidManifest(format!("program #{}: missing `{}`", i, name))
+        })
+    };
+    let pubkey = |name: &str| {
+        field(name).and_then(|value| {
+            Pubkey::from_str(value).map_err(|_| {
+                InputError::InvalidManifest(format!("program #{}: invalid `{}`", i, name))
+            })
+        })
+    };
+
+    let program_keypair = program["program_keypair"]
+        .as_str()
+        .map(|path| base.join(path));
+    let program_id = match (&program_keypair, program["program_id"].as_str()) {
+        (Some(path), None) => read_keypair(path)?.pubkey(),
+        (None, Some(_)) => pubkey("program_id")?,
+        (Some(path), Some(_)) => {
+            let program_id = pubkey("program_id")?;
+            if read_keypair(path)?.pubkey() != program_id {
+                return Err(InputError::InvalidManifest(format!(
+                    "program #{}: `program_keypair` does not match `program_id`",
+                    i
+                ))
+                .into());
+            }
+            program_id
+        }
+        (None, None) => {
+            return Err(InputError::InvalidManifest(format!(
+                "program #{}: either `program_keypair` or `program_id` is required",
+                i
+            ))
+            .into())
+        }
+    };
+
+    let max_len = match &program["max_len"] {
+        Yaml::Integer(max_len) => Some(usize::try_from(*max_len).map_err(|_| {
+            InputError::InvalidManifest(format!("program #{}: invalid `max_len`", i))
+        })?),
+        Yaml::BadValue => None,
+        _ => {
+            return Err(
+                InputError::InvalidManifest(format!("program #{}: invalid `max_len`", i)).into(),
+            )
+        }
+    };
+
+    Ok(ManifestProgram {
+        name: field("name")?.to_string(),
+        elf: base.join(field("elf")?),
+        program_id,
+        program_keypair,
+        authority: pubkey("authority")?,
+        max_len,
+    })
+}
+
+/// How a program was released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReleaseStatus {
+    /// Deployed from scratch
+    Deployed,
+    /// Upgraded by the payer, which was the upgrade authority
+    Upgraded,
+    /// Uploaded to a buffer owned by the upgrade authority, which still has to
+    /// upgrade the program, e.g. through a multisig
+    Buffered,
+}
+
+impl ReleaseStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReleaseStatus::Deployed => "deployed",
+            ReleaseStatus::Upgraded => "upgraded",
+            ReleaseStatus::Buffered => "buffered",
+        }
+    }
+}
+
+impl FromStr for ReleaseStatus {
+    type Err = InputError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "deployed" => Ok(ReleaseStatus::Deployed),
+            "upgraded" => Ok(ReleaseStatus::Upgraded),
+            "buffered" => Ok(ReleaseStatus::Buffered),
+            _ => Err(InputError::InvalidLockfile(format!(
+                "unknown status `{}`",
+                s
+            ))),
+        }
+    }
+}
+
+/// Released programs, as recorded in the lockfile.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lockfile {
+    pub programs: Vec<LockedProgram>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedProgram {
+    pub name: String,
+    pub program_id: Pubkey,
+    pub buffer: Pubkey,
+    /// Hex encoded SHA-256 of the ELF, as printed by `sha256sum`
+    pub elf_sha256: String,
+    pub elf_len: usize,
+    pub authority: Pubkey,
+    pub status: ReleaseStatus,
+    pub signatures: Vec<Signature>,
+    pub slot: Slot,
+}
+
+impl Lockfile {
+    /// Records a released program, replacing the entry of the same name.
+    pub fn insert(&mut self, program: LockedProgram) {
+        match self.programs.iter_mut().find(|p| p.name == program.name) {
+            Some(entry) => *entry = program,
+            None => self.programs.push(program),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|source| InputError::LockfileRead {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let document = utils::load_yaml(&content).map_err(InputError::InvalidLockfile)?;
+
+        let programs = match &document["programs"] {
+            Yaml::Array(programs) => programs,
+            _ => {
+                return Err(
+                    InputError::InvalidLockfile("`programs` must be a list".to_string()).into(),
+                )
+            }
+        };
+
+        let programs = programs
+            .iter()
+            .enumerate()
+            .map(|(i, program)| parse_locked_program(program, i))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(Self { programs })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let programs = self
+            .programs
+            .iter()
+            .map(|program| {
+                let mut entry = YamlHash::new();
+                let mut insert = |key: &str, value: Yaml| {
+                    entry.insert(Yaml::String(key.to_string()), value);
+                };
+                insert("name", Yaml::String(program.name.clone()));
+                insert("program_id", Yaml::String(program.program_id.to_string()));
+                insert("buffer", Yaml::String(program.buffer.to_string()));
+                insert("elf_sha256", Yaml::String(program.elf_sha256.clone()));
+                insert("elf_len", Yaml::Integer(program.elf_len as i64));
+                insert("authority", Yaml::String(program.authority.to_string()));
+                insert("status", Yaml::String(program.status.as_str().to_string()));
+                insert("slot", Yaml::Integer(program.slot as i64));
+                insert(
+                    "signatures",
+                    Yaml::Array(
+                        program
+                            .signatures
+                            .iter()
+                            .map(|signature| Yaml::String(signature.to_string()))
+                            .collect(),
+                    ),
+                );
+                Yaml::Hash(entry)
+            })
+            .collect();
+
+        let mut document = YamlHash::new();
+        document.insert(Yaml::String("programs".to_string()), Yaml::Array(programs));
+
+        let mut content = String::new();
+        YamlEmitter::new(&mut content)
+            .dump(&Yaml::Hash(document))
+            .map_err(|e| InputError::InvalidLockfile(format!("{:?}", e)))?;
+        content.push('\n');
+
+        // The lockfile is the release record, it is replaced by a complete
+        // file or not at all
+        utils::write_file_atomic(path, content.as_bytes(), 0o644).map_err(|source| {
+            InputError::LockfileWrite {
+                path: path.display().to_string(),
+                source,
+            }
+            .into()
+        })
+    }
+}
+
+fn parse_locked_program(
+    program: &Yaml,
+    i: usize,
+) -> std::result::Result<LockedProgram, InputError> {
+    let invalid =
+        |name: &str| InputError::InvalidLockfile(format!("program #{}: invalid `{}`", i, name));
+    let field = |name: &str| program[name].as_str().ok_or_else(|| invalid(name));
+    let pubkey = |name: &str| {
+        field(name).and_then(|value| Pubkey::from_str(value).map_err(|_| invalid(name)))
+    };
+    let number = |name: &str| {
+        program[name]
+            .as_i64()
+            .and_then(|value| u64::try_from(value).ok())
+            .ok_or_else(|| invalid(name))
+    };
+
+    let signatures = match &program["signatures"] {
+        Yaml::Array(signatures) => signatures
+            .iter()
+            .map(|signature| {
+                signature
+                    .as_str()
+                    .and_then(|signature| Signature::from_str(signature).ok())
+                    .ok_or_else(|| invalid("signatures"))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?,
+        _ => return Err(invalid("signatures")),
+    };
+
+    Ok(LockedProgram {
+        name: field("name")?.to_string(),
+        program_id: pubkey("program_id")?,
+        buffer: pubkey("buffer")?,
+        elf_sha256: field("elf_sha256")?.to_string(),
+        elf_len: number("elf_len")? as usize,
+        authority: pubkey("authority")?,
+        status: field("status")?.parse()?,
+        signatures,
+        slot: number("slot")?,
+    })
+}
+
+fn read_keypair(path: &Path) -> Result<Keypair> {
+    read_keypair_file(path).map_err(|e| {
+        SignerError::KeypairRead {
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        }
+        .into()
+    })
+}
+
+/// Releases the manifest programs in order. Missing programs are deployed,
+/// existing ones upgraded when `authority` is their upgrade authority, and
+/// otherwise uploaded to a buffer handed over to the upgrade authority.
+///
+/// `authority` signs for the buffers and upgrades, the payer only pays.
+///
+/// Released programs are merged into the existing lockfile, which is
+/// rewritten after every program, so an interrupted release still records
+/// the programs it completed. Without a lockfile path, e.g. for a dry run,
+/// nothing is recorded.
+pub async fn release<R: Rpc + ?Sized>(
+    manifest: &Manifest,
+    payer: &Keypair,
+    authority: &Keypair,
+    lockfile_path: Option<&Path>,
+    connection: &R,
+) -> Result<Lockfile> {
+    let mut lockfile = match lockfile_path {
+        Some(path) if path.exists() => Lockfile::from_file(path)?,
+        _ => Lockfile::default(),
+    };
+
+    for program in &manifest.programs {
+        utils::print_header("Releasing program");
+        println!("Name: {}", program.name);
+        println!("Program: {}", program.program_id);
+
+        let program_data = utils::read_elf(&program.elf.to_string_lossy())?;
+        let buffer = Keypair::new();
+
+        let (status, receipt) = match get_program_data(&program.program_id, connection).await? {
+            None => {
+                let program_keypair = match &program.program_keypair {
+                    Some(path) => read_keypair(path)?,
+                    None => {
+                        return Err(InputError::InvalidManifest(format!(
+                        "program `{}` does not exist, `program_keypair` is required to deploy it",
+                        program.name
+                    ))
+                        .into())
+                    }
+                };
+
+                let mut plan = DeployPlan::new(&program_data, payer, &program_keypair)
+                    .buffer(&buffer)
+                    .buffer_authority(authority)
+                    .final_authority(program.authority);
+                if let Some(max_len) = program.max_len {
+                    plan = plan.max_data_len(max_len);
+                }
+                let deploy_receipt = plan
+                    .deployer(connection)
+                    .on_checkpoint(utils::print_checkpoint)
+                    .run()
+                    .await?;
+
+                (ReleaseStatus::Deployed, deploy_receipt.receipt)
+            }
+            Some(current) => {
+                let upgrade_authority = current
+                    .upgrade_authority
+                    .ok_or(OnChainError::ImmutableProgram(program.program_id))?;
+                if upgrade_authority != authority.pubkey() && upgrade_authority != program.authority
+                {
+                    return Err(OnChainError::UnexpectedAuthority {
+                        address: program.program_id,
+                        expected: program.authority,
+                        actual: upgrade_authority,
+                    }
+                    .into());
+                }
+
+                let mut receipt = Receipt::default();
+
+                // The upgrade fails if the program outgrew its ProgramData
+                // account, which only grows through an extend
+                let capacity = current.data.len();
+                let required = program.max_len.unwrap_or(0).max(program_data.len());
+                if required > capacity {
+                    let additional_bytes = u32::try_from(required - capacity).map_err(|_| {
+                        InputError::InvalidManifest(format!(
+                            "program `{}`: invalid `max_len`",
+                            program.name
+                        ))
+                    })?;
+                    receipt.merge(
+                        extend_program(payer, &program.program_id, additional_bytes, connection)
+                            .await?,
+                    );
+                }
+
+                receipt.merge(
+                    create_buffer(
+                        payer,
+                        &buffer,
+                        &authority.pubkey(),
+                        program_data.len(),
+                        connection,
+                    )
+                    .await?,
+                );
+                receipt.merge(
+                    write_buffer(
+                        payer,
+                        authority,
+                        &buffer.pubkey(),
+                        &program_data,
+                        connection,
+                    )
+                    .await?,
+                );
+
+                if upgrade_authority == authority.pubkey() {
+                    receipt.merge(
+                        upgrade(
+                            payer,
+                            authority,
+                            &program.program_id,
+                            &buffer.pubkey(),
+                            connection,
+                        )
+                        .await?,
+                    );
+                    if program.authority != authority.pubkey() {
+                        receipt.merge(
+                            set_program_authority(
+                                payer,
+                                authority,
+                                &program.program_id,
+                                &program.authority,
+                                connection,
+                            )
+                            .await?,
+                        );
+                    }
+                    (ReleaseStatus::Upgraded, receipt)
+                } else {
+                    receipt.merge(
+                        set_buffer_authority(
+                            payer,
+                            authority,
+                            &buffer.pubkey(),
+                            &upgrade_authority,
+                            connection,
+                        )
+                        .await?,
+                    );
+                    (ReleaseStatus::Buffered, receipt)
+                }
+            }
+        };
+        utils::print_receipt(&receipt);
+
+        lockfile.insert(LockedProgram {
+            name: program.name.clone(),
+            program_id: program.program_id,
+            buffer: buffer.pubkey(),
+            elf_sha256: hex::encode(hash(&program_data).to_bytes()),
+            elf_len: program_data.len(),
+            authority: program.authority,
+            status,
+            signatures: receipt.signatures,
+            slot: receipt.slot,
+        });
+        if let Some(path) = lockfile_path {
+            lockfile.write(path)?;
+        }
+    }
+
+    Ok(lockfile)
+}
+
+/// A lockfile entry that does not match the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub name: String,
+    pub reason: String,
+}
+
+/// Compares the chain with the lockfile. Buffered programs match while their
+/// buffer is intact or once the program runs the locked ELF.
+pub async fn check<R: Rpc + ?Sized>(lockfile: &Lockfile, connection: &R) -> Result<Vec<Mismatch>> {
+    let mut mismatches = Vec::new();
+
+    for program in &lockfile.programs {
+        let mut mismatch = |reason: String| {
+            mismatches.push(Mismatch {
+                name: program.name.clone(),
+                reason,
+            })
+        };
+
+        let program_data = get_program_data(&program.program_id, connection).await?;
+        let program_matches = match &program_data {
+            Some(program_data) => matches_elf(&program_data.data, program),
+            None => false,
+        };
+
+        if program.status == ReleaseStatus::Buffered && !program_matches {
+            match get_buffer(&program.buffer, connection).await {
+                Ok(buffer) if matches_elf(&buffer.data, program) => {
+                    println!(
+                        "{}: buffer ({}) awaits upgrade by ({})",
+                        program.name,
+                        program.buffer,
+                        buffer.authority.map(|a| a.to_string()).unwrap_or_default()
+                    );
+                }
+                Ok(_) => mismatch(format!("buffer ({}) content differs", program.buffer)),
+                Err(_) => mismatch(format!(
+                    "program is not upgraded and buffer ({}) is gone",
+                    program.buffer
+                )),
+            }
+            continue;
+        }
+
+        match program_data {
+            None => mismatch(format!("program ({}) not found", program.program_id)),
+            Some(program_data) => {
+                if !program_matches {
+                    mismatch("program data differs from the locked ELF".to_string());
+                }
+                if program_data.upgrade_authority != Some(program.authority) {
+                    mismatch(format!(
+                        "upgrade authority is ({}), expected ({})",
+                        program_data
+                            .upgrade_authority
+                            .map(|a| a.to_string())
+                            .unwrap_or_else(|| "none".to_string()),
+                        program.authority
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Account data holds the locked ELF followed by zero padding.
+fn matches_elf(data: &[u8], program: &LockedProgram) -> bool {
+    data.len() >= program.elf_len
+        && hex::encode(hash(&data[..program.elf_len]).to_bytes()) == program.elf_sha256
+        && data[program.elf_len..].iter().all(|byte| *byte == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::write_keypair_file;
+
+    use super::*;
+    use crate::error::Error;
+
+    /// Writes `manifest` and a program keypair into a fresh directory.
+    fn manifest_dir(manifest: impl Fn(&Keypair) -> String) -> (PathBuf, Keypair) {
+        let keypair = Keypair::new();
+        let dir = std::env::temp_dir().join(format!("release-{}", keypair.pubkey()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_keypair_file(&keypair, dir.join("program.json")).unwrap();
+        std::fs::write(dir.join("release.yml"), manifest(&keypair)).unwrap();
+        (dir, keypair)
+    }
+
+    fn assert_invalid_manifest(result: Result<Manifest>, expected: &str) {
+        match result {
+            Err(Error::Input(InputError::InvalidManifest(reason))) => assert!(
+                reason.contains(expected),
+                "`{}` does not mention `{}`",
+                reason,
+                expected
+            ),
+            result => panic!("expected an invalid manifest, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parse_manifest() {
+        let authority = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let (dir, keypair) = manifest_dir(|keypair| {
+            format!(
+                "programs:\n\
+                 \x20 - name: round_loader\n\
+                 \x20   elf: round_loader.so\n\
+                 \x20   program_keypair: program.json\n\
+                 \x20   program_id: \"{}\"\n\
+                 \x20   authority: \"{}\"\n\
+                 \x20   max_len: 400000\n\
+                 \x20 - name: token_proxy\n\
+                 \x20   elf: token_proxy.so\n\
+                 \x20   program_id: \"{}\"\n\
+                 \x20   authority: \"{}\"\n",
+                keypair.pubkey(),
+                authority,
+                program_id,
+                authority
+            )
+        });
+
+        let manifest = Manifest::from_file(&dir.join("release.yml")).unwrap();
+        assert_eq!(
+            manifest.programs,
+            vec![
+                ManifestProgram {
+                    name: "round_loader".to_string(),
+                    elf: dir.join("round_loader.so"),
+                    program_id: keypair.pubkey(),
+                    program_keypair: Some(dir.join("program.json")),
+                    authority,
+                    max_len: Some(400000),
+                },
+                ManifestProgram {
+                    name: "token_proxy".to_string(),
+                    elf: dir.join("token_proxy.so"),
+                    program_id,
+                    program_keypair: None,
+                    authority,
+                    max_len: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_keypair_not_matching_program_id() {
+        let (dir, _) = manifest_dir(|_| {
+            format!(
+                "programs:\n\
+                 \x20 - name: round_loader\n\
+                 \x20   elf: round_loader.so\n\
+                 \x20   program_keypair: program.json\n\
+                 \x20   program_id: \"{}\"\n\
+                 \x20   authority: \"{}\"\n",
+                Pubkey::new_unique(),
+                Pubkey::new_unique()
+            )
+        });
+
+        assert_invalid_manifest(
+            Manifest::from_file(&dir.join("release.yml")),
+            "`program_keypair` does not match `program_id`",
+        );
+    }
+
+    #[test]
+    fn reject_invalid_max_len() {
+        for max_len in ["-1", "\"400000\""] {
+            let (dir, _) = manifest_dir(|keypair| {
+                format!(
+                    "programs:\n\
+                     \x20 - name: round_loader\n\
+                     \x20   elf: round_loader.so\n\
+                     \x20   program_id: \"{}\"\n\
+                     \x20   authority: \"{}\"\n\
+                     \x20   max_len: {}\n",
+                    keypair.pubkey(),
+                    Pubkey::new_unique(),
+                    max_len
+                )
+            });
+
+            assert_invalid_manifest(
+                Manifest::from_file(&dir.join("release.yml")),
+                "invalid `max_len`",
+            );
+        }
+    }
+
+    fn locked_program(name: &str, status: ReleaseStatus) -> LockedProgram {
+        LockedProgram {
+            name: name.to_string(),
+            program_id: Pubkey::new_unique(),
+            buffer: Pubkey::new_unique(),
+            elf_sha256: hex::encode(hash(name.as_bytes()).to_bytes()),
+            elf_len: 1024,
+            authority: Pubkey::new_unique(),
+            status,
+            signatures: vec![Signature::new_unique(), Signature::new_unique()],
+            slot: 42,
+        }
+    }
+
+    #[test]
+    fn lockfile_round_trip() {
+        let lockfile = Lockfile {
+            programs: vec![
+                locked_program("round_loader", ReleaseStatus::Deployed),
+                locked_program("token_proxy", ReleaseStatus::Buffered),
+                locked_program("bridge", ReleaseStatus::Upgraded),
+            ],
+        };
+
+        let path = std::env::temp_dir().join(format!("{}.lock", lockfile.programs[0].program_id));
+        lockfile.write(&path).unwrap();
+        assert_eq!(Lockfile::from_file(&path).unwrap(), lockfile);
+    }
+
+    #[test]
+    fn lockfile_insert_replaces_by_name() {
+        let mut lockfile = Lockfile {
+            programs: vec![
+                locked_program("round_loader", ReleaseStatus::Deployed),
+                locked_program("token_proxy", ReleaseStatus::Deployed),
+            ],
+        };
+
+        let upgraded = locked_program("round_loader", ReleaseStatus::Upgraded);
+        lockfile.insert(upgraded.clone());
+        let added = locked_program("bridge", ReleaseStatus::Deployed);
+        lockfile.insert(added.clone());
+
+        assert_eq!(lockfile.programs.len(), 3);
+        assert_eq!(lockfile.programs[0], upgraded);
+        assert_eq!(lockfile.programs[1].name, "token_proxy");
+        assert_eq!(lockfile.programs[2], added);
+    }
+}
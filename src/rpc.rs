@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use solana_banks_client::BanksClient;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::nonblocking::tpu_client::TpuClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_client::rpc_request::{
+    RpcError, RpcResponseErrorData, MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS,
+};
+use solana_client::tpu_client::{TpuClientConfig, TpuSenderError};
+use solana_program::message::Message;
+use solana_sdk::account::Account;
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+use crate::client::Receipt;
+use crate::error::{Error, PartialWriteError, Result, SignerError};
+use crate::program_error::decode_transaction_error;
+use crate::utils;
+
+/// Blockhashes a batch of messages is signed with before giving up.
+const MAX_BLOCKHASH_RETRIES: usize = 5;
+
+/// Interval at which pending transactions are sent again.
+const TRANSACTION_RESEND_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Outcome of a simulated transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Simulation {
+    pub err: Option<TransactionError>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+}
+
+/// RPC operations used by the client.
+///
+/// Implemented for the nonblocking `RpcClient` talking to a live cluster and
+/// for `BanksClient`, which runs the same flows in-process with
+/// `solana-program-test`.
+#[async_trait]
+pub trait Rpc: Send + Sync {
+    async fn get_latest_blockhash(&self) -> Result<Hash>;
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64>;
+
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<Account>>;
+
+    async fn get_balance(&self, address: &Pubkey) -> Result<u64>;
+
+    async fn get_fee_for_message(&self, message: &Message) -> Result<u64>;
+
+    async fn simulate_transaction(&self, transaction: &Transaction) -> Result<Simulation>;
+
+    /// Sends a signed transaction and waits for its confirmation.
+    async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Receipt>;
+
+    /// Signs and sends a batch of independent messages, e.g. write chunks.
+    async fn send_and_confirm_messages(
+        &self,
+        messages: &[Message],
+        signers: &[&Keypair],
+    ) -> Result<Receipt>;
+}
+
+#[async_trait]
+impl Rpc for Arc<RpcClient> {
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(RpcClient::get_latest_blockhash(self).await?)
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        Ok(RpcClient::get_minimum_balance_for_rent_exemption(self, data_len).await?)
+    }
+
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        Ok(self
+            .get_account_with_commitment(address, self.commitment())
+            .await?
+            .value)
+    }
+
+    async fn get_balance(&self, address: &Pubkey) -> Result<u64> {
+        Ok(RpcClient::get_balance(self, address).await?)
+    }
+
+    async fn get_fee_for_message(&self, message: &Message) -> Result<u64> {
+        Ok(RpcClient::get_fee_for_message(self, message).await?)
+    }
+
+    async fn simulate_transaction(&self, transaction: &Transaction) -> Result<Simulation> {
+        let result = RpcClient::simulate_transaction(self, transaction)
+            .await?
+            .value;
+
+        Ok(Simulation {
+            err: result.err,
+            logs: result.logs.unwrap_or_default(),
+            units_consumed: result.units_consumed,
+        })
+    }
+
+    async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Receipt> {
+        // Looked up before sending, the blockhash may expire once it landed
+        let fee = Rpc::get_fee_for_message(self, &transaction.message).await?;
+
+        let signature = self
+            .send_and_confirm_transaction_with_spinner(transaction)
+            .await
+            .map_err(|e| decode_client_error(e, &transaction.message))?;
+
+        // The transaction is confirmed, failing to read its slot back must not
+        // report it as failed
+        let slot = match self.get_signature_statuses(&[signature]).await {
+            Ok(statuses) => statuses
+                .value
+                .into_iter()
+                .flatten()
+                .map(|status| status.slot)
+                .next(),
+            Err(e) => {
+                eprintln!(
+                    "Warning: failed to read the status of ({}): {}",
+                    signature, e
+                );
+                None
+            }
+        };
+        let slot = slot.unwrap_or_else(|| {
+            eprintln!("Warning: slot of ({}) is unknown", signature);
+            0
+        });
+
+        Ok(Receipt {
+            signatures: vec![signature],
+            slot,
+            fee,
+            simulated: false,
+        })
+    }
+
+    async fn send_and_confirm_messages(
+        &self,
+        messages: &[Message],
+        signers: &[&Keypair],
+    ) -> Result<Receipt> {
+        let mut fee = 0;
+        for message in messages {
+            fee += Rpc::get_fee_for_message(self, message).await?;
+        }
+
+        let websocket_url = utils::get_ws_url()?;
+        let mut tpu_client = TpuClient::new(
+            "solana-cli",
+            self.clone(),
+            &websocket_url,
+            TpuClientConfig::default(),
+        )
+        .await?;
+
+        // The TPU client's own batch sender does not report which signatures
+        // landed, so transactions are signed, sent and confirmed here. Those
+        // still pending when their blockhash expires are signed again.
+        let mut landed = vec![None; messages.len()];
+        let outcome: Result<()> = async {
+            let mut pending = (0..messages.len()).collect::<Vec<_>>();
+            for _ in 0..MAX_BLOCKHASH_RETRIES {
+                let (blockhash, last_valid_block_height) = self
+                    .get_latest_blockhash_with_commitment(self.commitment())
+                    .await?;
+
+                let mut transactions = HashMap::new();
+                for i in pending {
+                    let mut transaction = Transaction::new_unsigned(messages[i].clone());
+                    transaction
+                        .try_sign(signers, blockhash)
+                        .map_err(|e| SignerError::Sign(e.to_string()))?;
+                    transactions.insert(transaction.signatures[0], (i, transaction));
+                }
+
+                while !transactions.is_empty()
+                    && self.get_block_height().await? <= last_valid_block_height
+                {
+                    for (_, transaction) in transactions.values() {
+                        if !tpu_client.send_transaction(transaction).await {
+                            let _ = RpcClient::send_transaction(self, transaction).await;
+                        }
+                    }
+                    tokio::time::sleep(TRANSACTION_RESEND_INTERVAL).await;
+
+                    let signatures = transactions.keys().copied().collect::<Vec<_>>();
+                    for chunk in signatures.chunks(MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS) {
+                        let statuses = self.get_signature_statuses(chunk).await?.value;
+                        for (signature, status) in chunk.iter().zip(statuses) {
+                            let status = match status {
+                                Some(status) if status.satisfies_commitment(self.commitment()) => {
+                                    status
+                                }
+                                _ => continue,
+                            };
+                            if let Some((i, _)) = transactions.remove(signature) {
+                                landed[i] = Some((*signature, status.slot, status.err));
+                            }
+                        }
+                    }
+                }
+
+                pending = transactions.into_values().map(|(i, _)| i).collect();
+                if pending.is_empty() {
+                    return Ok(());
+                }
+                println!(
+                    "Blockhash expired, ({}) transactions pending",
+                    pending.len()
+                );
+            }
+
+            Err(TpuSenderError::Custom("blockhash expired too many times".to_string()).into())
+        }
+        .await;
+
+        tpu_client.shutdown().await;
+        outcome?;
+
+        let landed = landed.into_iter().flatten().collect::<Vec<_>>();
+        let transaction_errors = landed
+            .iter()
+            .zip(messages)
+            .filter_map(|((signature, _, error), message)| {
+                error.clone().map(|error| (*signature, error, message))
+            })
+            .collect::<Vec<_>>();
+
+        if !transaction_errors.is_empty() {
+            for (signature, transaction_error, message) in &transaction_errors {
+                // Sent without preflight, so the logs are only on chain
+                match get_transaction_logs(self, signature).await {
+                    Ok(logs) => utils::print_program_logs(&logs),
+                    Err(e) => {
+                        eprintln!("Warning: failed to read the logs of ({}): {}", signature, e)
+                    }
+                }
+                eprintln!(
+                    "{}",
+                    decode_transaction_error(transaction_error.clone(), message)
+                );
+            }
+            return Err(PartialWriteError {
+                total: messages.len(),
+                errors: transaction_errors
+                    .into_iter()
+                    .map(|(_, error, _)| error)
+                    .collect(),
+            }
+            .into());
+        }
+
+        Ok(Receipt {
+            signatures: landed.iter().map(|(signature, _, _)| *signature).collect(),
+            slot: landed
+                .iter()
+                .map(|(_, slot, _)| *slot)
+                .max()
+                .unwrap_or_default(),
+            fee,
+            simulated: false,
+        })
+    }
+}
+
+#[async_trait]
+impl Rpc for BanksClient {
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(BanksClient::get_latest_blockhash(&mut self.clone()).await?)
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        let rent = self.clone().get_rent().await?;
+        Ok(rent.minimum_balance(data_len))
+    }
+
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        Ok(BanksClient::get_account(&mut self.clone(), *address).await?)
+    }
+
+    async fn get_balance(&self, address: &Pubkey) -> Result<u64> {
+        Ok(BanksClient::get_balance(&mut self.clone(), *address).await?)
+    }
+
+    async fn get_fee_for_message(&self, message: &Message) -> Result<u64> {
+        Ok(
+            BanksClient::get_fee_for_message(&mut self.clone(), message.clone())
+                .await?
+                .unwrap_or_default(),
+        )
+    }
+
+    async fn simulate_transaction(&self, transaction: &Transaction) -> Result<Simulation> {
+        let result =
+            BanksClient::simulate_transaction(&mut self.clone(), transaction.clone()).await?;
+        let details = result.simulation_details;
+
+        Ok(Simulation {
+            err: result.result.and_then(|result| result.err()),
+            logs: details
+                .as_ref()
+                .map(|details| details.logs.clone())
+                .unwrap_or_default(),
+            units_consumed: details.map(|details| details.units_consumed),
+        })
+    }
+
+    async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Receipt> {
+        let mut client = self.clone();
+
+        let fee = Rpc::get_fee_for_message(self, &transaction.message).await?;
+        client
+            .process_transaction(transaction.clone())
+            .await
+            .map_err(|e| decode_banks_client_error(e, &transaction.message))?;
+
+        Ok(Receipt {
+            signatures: vec![transaction.signatures[0]],
+            slot: client.get_root_slot().await?,
+            fee,
+            simulated: false,
+        })
+    }
+
+    async fn send_and_confirm_messages(
+        &self,
+        messages: &[Message],
+        signers: &[&Keypair],
+    ) -> Result<Receipt> {
+        let mut receipt = Receipt::default();
+        for message in messages {
+            let transaction =
+                Transaction::try_new(signers, message.clone(), message.recent_blockhash)
+                    .map_err(|e| SignerError::Sign(e.to_string()))?;
+            let chunk = Rpc::send_and_confirm_transaction(self, &transaction).await?;
+
+            receipt.signatures.extend(chunk.signatures);
+            receipt.slot = chunk.slot;
+            receipt.fee += chunk.fee;
+        }
+
+        Ok(receipt)
+    }
+}
+
+/// Reads the program logs of a landed transaction.
+async fn get_transaction_logs(
+    client: &RpcClient,
+    signature: &Signature,
+) -> std::result::Result<Vec<String>, ClientError> {
+    let transaction = client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: None,
+                commitment: Some(client.commitment()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?;
+
+    Ok(transaction
+        .transaction
+        .meta
+        .and_then(|meta| Option::<Vec<String>>::from(meta.log_messages))
+        .unwrap_or_default())
+}
+
+/// Prints the preflight logs of a failed transaction and names the failed
+/// instruction.
+fn decode_client_error(error: ClientError, message: &Message) -> Error {
+    if let ClientErrorKind::RpcError(RpcError::RpcResponseError {
+        data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+        ..
+    }) = error.kind()
+    {
+        if let Some(logs) = &result.logs {
+            utils::print_program_logs(logs);
+        }
+    }
+
+    match error.get_transaction_error() {
+        Some(transaction_error) => decode_transaction_error(transaction_error, message).into(),
+        None => error.into(),
+    }
+}
+
+fn decode_banks_client_error(
+    error: solana_banks_client::BanksClientError,
+    message: &Message,
+) -> Error {
+    use solana_banks_client::BanksClientError;
+
+    match error {
+        BanksClientError::SimulationError { err, logs, .. } => {
+            utils::print_program_logs(&logs);
+            decode_transaction_error(err, message).into()
+        }
+        BanksClientError::TransactionError(err) => decode_transaction_error(err, message).into(),
+        error => error.into(),
+    }
+}
+
+/// Transport that simulates every transaction instead of sending it.
+///
+/// Reads are forwarded to the inner transport. Of a batch of messages only
+/// the first and the last are simulated.
+///
+/// Since nothing is broadcast, a step writing to an account an earlier step
+/// would have created can not succeed. Its failure is reported as skipped
+/// and the run goes on. Any other failed simulation is returned as the error
+/// sending the transaction would have failed with.
+pub struct DryRun<R> {
+    inner: R,
+    steps: Mutex<DryRunSteps>,
+}
+
+#[derive(Default)]
+struct DryRunSteps {
+    count: usize,
+    /// Accounts missing on chain, with the step that would have created them
+    created: HashMap<Pubkey, (usize, Signature)>,
+}
+
+impl<R: Rpc> DryRun<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            steps: Mutex::new(DryRunSteps::default()),
+        }
+    }
+
+    async fn simulate(&self, transaction: &Transaction) -> Result<Receipt> {
+        let message = &transaction.message;
+
+        let mut missing = Vec::new();
+        for (i, key) in message.account_keys.iter().enumerate() {
+            if message.is_writable(i) && self.inner.get_account(key).await?.is_none() {
+                missing.push(*key);
+            }
+        }
+
+        let (step, depends_on) = {
+            let mut steps = self.steps.lock().unwrap();
+            steps.count += 1;
+            let step = steps.count;
+            let depends_on = missing
+                .iter()
+                .find_map(|key| steps.created.get(key).copied());
+            for key in missing {
+                steps
+                    .created
+                    .entry(key)
+                    .or_insert((step, transaction.signatures[0]));
+            }
+            (step, depends_on)
+        };
+
+        let simulation = self.inner.simulate_transaction(transaction).await?;
+        if let (Some(_), Some((previous, signature))) = (&simulation.err, depends_on) {
+            println!(
+                "Step #{} skipped: depends on step #{} ({})",
+                step, previous, signature
+            );
+        } else {
+            utils::print_simulation(&simulation, message);
+            if let Some(err) = simulation.err {
+                return Err(decode_transaction_error(err, message).into());
+            }
+        }
+
+        Ok(Receipt {
+            signatures: transaction.signatures.clone(),
+            slot: 0,
+            fee: self.inner.get_fee_for_message(message).await?,
+            simulated: true,
+        })
+    }
+}
+
+#[async_trait]
+impl<R: Rpc> Rpc for DryRun<R> {
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        self.inner.get_latest_blockhash().await
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        self.inner
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await
+    }
+
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        self.inner.get_account(address).await
+    }
+
+    async fn get_balance(&self, address: &Pubkey) -> Result<u64> {
+        self.inner.get_balance(address).await
+    }
+
+    async fn get_fee_for_message(&self, message: &Message) -> Result<u64> {
+        self.inner.get_fee_for_message(message).await
+    }
+
+    async fn simulate_transaction(&self, transaction: &Transaction) -> Result<Simulation> {
+        self.inner.simulate_transaction(transaction).await
+    }
+
+    async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Receipt> {
+        self.simulate(transaction).await
+    }
+
+    async fn send_and_confirm_messages(
+        &self,
+        messages: &[Message],
+        signers: &[&Keypair],
+    ) -> Result<Receipt> {
+        let mut receipt = Receipt::default();
+        for message in messages {
+            receipt.fee += self.inner.get_fee_for_message(message).await?;
+        }
+
+        let mut simulated = Vec::new();
+        simulated.extend(messages.first());
+        if messages.len() > 1 {
+            simulated.extend(messages.last());
+        }
+
+        for message in simulated {
+            let transaction =
+                Transaction::try_new(signers, message.clone(), message.recent_blockhash)
+                    .map_err(|e| SignerError::Sign(e.to_string()))?;
+            let chunk = self.simulate(&transaction).await?;
+            receipt.signatures.extend(chunk.signatures);
+        }
+        receipt.simulated = true;
+
+        Ok(receipt)
+    }
+}
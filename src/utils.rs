@@ -1,15 +1,24 @@
 use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
 use yaml_rust::YamlLoader;
 
+use solana_bridge::bridge_types::Vote;
+use solana_bridge::round_loader::{RelayRound, RelayRoundProposal, Settings};
+
 use solana_program::message::Message;
 use solana_sdk::signature::{Keypair, Signature};
 use solana_sdk::signer::keypair::read_keypair_file;
 use solana_sdk::transaction::Transaction;
 
-use crate::error::{Error, Result};
+use crate::client::Receipt;
+use crate::deploy::{Checkpoint, DeployReceipt};
+use crate::diff::ProgramDiff;
+use crate::elf;
+use crate::error::{ConfigError, InputError, Result, SignerError};
+use crate::program_error;
+use crate::rpc::Simulation;
 
 pub fn get_config() -> Result<yaml_rust::Yaml> {
     let path = match home::home_dir() {
@@ -18,30 +27,72 @@ pub fn get_config() -> Result<yaml_rust::Yaml> {
             path
         }
         None => {
-            return Err(Error::ConfigReadError(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "failed to locate homedir and thus can not locoate solana config",
-            )));
+            return Err(ConfigError::HomeDirNotFound.into());
         }
     };
-    let config = std::fs::read_to_string(path).map_err(Error::ConfigReadError)?;
-    let mut config = YamlLoader::load_from_str(&config)?;
+    let config = std::fs::read_to_string(&path).map_err(|source| ConfigError::Read {
+        path: path.display().to_string(),
+        source,
+    })?;
+    let mut config = YamlLoader::load_from_str(&config).map_err(ConfigError::Parse)?;
     match config.len() {
         1 => Ok(config.remove(0)),
-        l => Err(Error::InvalidConfig(format!(
-            "expected one yaml document got ({})",
-            l
-        ))),
+        l => Err(ConfigError::Invalid(format!("expected one yaml document got ({})", l)).into()),
     }
 }
 
+/// Loads a single YAML (or JSON) document.
+pub fn load_yaml(content: &str) -> std::result::Result<yaml_rust::Yaml, String> {
+    let mut documents =
+        YamlLoader::load_from_str(content).map_err(|e| format!("failed to parse file: ({})", e))?;
+    match documents.len() {
+        1 => Ok(documents.remove(0)),
+        l => Err(format!("expected one document got ({})", l)),
+    }
+}
+
+/// Replaces `path` through a synced temporary file in the same directory, so
+/// it holds either its previous content or all of `content`. `mode` sets the
+/// permissions of the new file on unix.
+pub fn write_file_atomic(path: &Path, content: &[u8], mode: u32) -> std::io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut file = options.open(&temp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::rename(&temp_path, path)?;
+    // Persists the rename itself
+    #[cfg(unix)]
+    {
+        let directory = path
+            .parent()
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        File::open(directory)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
 pub fn get_rpc_url() -> Result<String> {
     let config = get_config()?;
     match config["json_rpc_url"].as_str() {
         Some(s) => Ok(s.to_string()),
-        None => Err(Error::InvalidConfig(
-            "missing `json_rpc_url` field".to_string(),
-        )),
+        None => Err(ConfigError::Invalid("missing `json_rpc_url` field".to_string()).into()),
     }
 }
 
@@ -49,9 +100,7 @@ pub fn get_ws_url() -> Result<String> {
     let config = get_config()?;
     match config["websocket_url"].as_str() {
         Some(s) => Ok(s.to_string()),
-        None => Err(Error::InvalidConfig(
-            "missing `websocket_url` field".to_string(),
-        )),
+        None => Err(ConfigError::Invalid("missing `websocket_url` field".to_string()).into()),
     }
 }
 
@@ -60,21 +109,31 @@ pub fn get_payer() -> Result<Keypair> {
     let path = match config["keypair_path"].as_str() {
         Some(s) => s,
         None => {
-            return Err(Error::InvalidConfig(
-                "missing `keypair_path` field".to_string(),
-            ))
+            return Err(ConfigError::Invalid("missing `keypair_path` field".to_string()).into())
         }
     };
     read_keypair_file(path).map_err(|e| {
-        Error::InvalidConfig(format!("failed to read keypair file ({}): ({})", path, e))
+        SignerError::KeypairRead {
+            path: path.to_string(),
+            reason: e.to_string(),
+        }
+        .into()
     })
 }
 
+/// Reads a program and validates it, see [`elf::validate_elf`].
 pub fn read_elf(program_location: &str) -> Result<Vec<u8>> {
-    let mut file = File::open(program_location).map_err(Error::ProgramOpenError)?;
+    let program_read_error = |source| InputError::ProgramRead {
+        path: program_location.to_string(),
+        source,
+    };
+
+    let mut file = File::open(program_location).map_err(program_read_error)?;
     let mut program_data = Vec::new();
     file.read_to_end(&mut program_data)
-        .map_err(Error::ProgramReadError)?;
+        .map_err(program_read_error)?;
+
+    elf::validate_elf(&program_data)?;
 
     Ok(program_data)
 }
@@ -98,6 +157,20 @@ where
         .saturating_sub(1)
 }
 
+/// Deployment journal kept next to the program, see [`crate::journal::Journal`].
+pub fn get_journal_file(program_path: &str) -> PathBuf {
+    let mut journal_file = PathBuf::new();
+    journal_file.push(program_path);
+
+    let mut filename = journal_file.file_stem().unwrap().to_os_string();
+    filename.push("-journal");
+
+    journal_file.set_file_name(filename);
+    journal_file.set_extension("yml");
+
+    journal_file
+}
+
 pub fn get_keypair_file(program_path: &str) -> PathBuf {
     let mut keypair_file = PathBuf::new();
     keypair_file.push(program_path);
@@ -120,3 +193,198 @@ pub fn print_header(header: &'static str) {
     println!("===================================");
     println!();
 }
+
+pub fn print_seed_phrase(seed_phrase: &str) {
+    let divider = "=".repeat(seed_phrase.len().max(40));
+    println!("{}", divider);
+    println!("Save this seed phrase to recover the program keypair:");
+    println!("{}", seed_phrase);
+    println!("{}", divider);
+}
+
+pub fn print_receipt(receipt: &Receipt) {
+    let label = if receipt.simulated {
+        "Simulated signature"
+    } else {
+        "Signature"
+    };
+    for signature in &receipt.signatures {
+        println!("{}: {}", label, signature);
+    }
+    println!("Slot: {}", receipt.slot);
+    println!("Fee: {} lamports", receipt.fee);
+}
+
+pub fn print_checkpoint(checkpoint: Checkpoint) {
+    match checkpoint {
+        Checkpoint::CostChecked(_) => {}
+        Checkpoint::BufferCreated { receipt, .. }
+        | Checkpoint::BufferWritten { receipt, .. }
+        | Checkpoint::BufferAuthoritySet { receipt, .. }
+        | Checkpoint::ProgramDeployed { receipt, .. }
+        | Checkpoint::AuthoritySet { receipt, .. } => print_receipt(receipt),
+    }
+}
+
+pub fn print_deploy_receipt(receipt: &DeployReceipt) {
+    print_header("Deployment complete");
+    println!("Program: {}", receipt.program_id);
+    println!("ProgramData: {}", receipt.program_data_address);
+    println!("Buffer: {}", receipt.buffer);
+    println!("Upgrade authority: {}", receipt.upgrade_authority);
+    println!("Max data length: {} bytes", receipt.max_data_len);
+    println!("Slot: {}", receipt.receipt.slot);
+    println!("Fees: {} lamports", receipt.receipt.fee);
+    if let Some(cost) = &receipt.cost {
+        println!(
+            "Rent: {} lamports",
+            cost.programdata_rent + cost.program_rent
+        );
+    }
+}
+
+pub fn print_program_diff(diff: &ProgramDiff) {
+    let size = |size: Option<usize>| size.map(|size| size.to_string()).unwrap_or_default();
+
+    print_header("Sections");
+    println!(
+        "{:<24} {:>10} {:>10}  Change",
+        "Name", "Deployed", "Candidate"
+    );
+    for section in &diff.sections {
+        println!(
+            "{:<24} {:>10} {:>10}  {:?}",
+            section.name,
+            size(section.deployed_size),
+            size(section.candidate_size),
+            section.change
+        );
+    }
+
+    print_header("Changed symbols");
+    for symbol in &diff.symbols {
+        println!(
+            "{:?} {} ({} -> {} bytes)",
+            symbol.change,
+            symbol.name,
+            size(symbol.deployed_size),
+            size(symbol.candidate_size)
+        );
+    }
+
+    print_header(".rodata strings");
+    for string in &diff.rodata_removed {
+        println!("- {:?}", string);
+    }
+    for string in &diff.rodata_added {
+        println!("+ {:?}", string);
+    }
+
+    print_header("Capacity");
+    println!("Deployed: {} bytes", diff.deployed_len);
+    println!("Candidate: {} bytes", diff.candidate_len);
+    println!("ProgramData capacity: {} bytes", diff.capacity);
+    if diff.fits() {
+        println!("Candidate fits the current ProgramData account");
+    } else {
+        println!(
+            "Candidate does not fit, extend the program by {} bytes before upgrading",
+            diff.candidate_len - diff.capacity
+        );
+    }
+}
+
+pub fn print_simulation(simulation: &Simulation, message: &Message) {
+    println!("Simulation logs:");
+    for log in &simulation.logs {
+        println!("  {}", log);
+    }
+    if let Some(units_consumed) = simulation.units_consumed {
+        println!("Compute units consumed: {}", units_consumed);
+    }
+    match &simulation.err {
+        Some(err) => println!(
+            "Simulation error: {}",
+            program_error::decode_transaction_error(err.clone(), message)
+        ),
+        None => println!("Simulation succeeded"),
+    }
+}
+
+/// Prints the program log lines explaining a failed transaction.
+pub fn print_program_logs(logs: &[String]) {
+    let mut logs = program_error::relevant_logs(logs).peekable();
+    if logs.peek().is_none() {
+        return;
+    }
+
+    eprintln!("Program logs:");
+    for log in logs {
+        eprintln!("  {}", log);
+    }
+}
+
+/// Prints a relay round proposal. When the relay round it was created in is
+/// known, votes are listed next to the relays that cast them.
+pub fn print_proposal(proposal: &RelayRoundProposal, relay_round: Option<&RelayRound>) {
+    let event = &proposal.event.data;
+    let meta = &proposal.meta.data;
+
+    println!("Author: {}", proposal.author);
+    println!("Round number: {}", proposal.round_number);
+    println!("Finalized: {}", proposal.is_initialized);
+    println!("Executed: {}", proposal.is_executed);
+    println!();
+    println!("Event timestamp: {}", meta.event_timestamp);
+    println!("Event transaction lt: {}", meta.event_transaction_lt);
+    println!("Event configuration: {}", meta.event_configuration);
+    println!();
+    println!("Proposal round number: {}", event.round_num);
+    println!("Proposal round end: {}", event.round_end);
+    println!("Proposal relays:");
+    for relay in &event.relays {
+        println!("  {}", relay);
+    }
+    println!();
+
+    print_votes(proposal, relay_round);
+}
+
+pub fn print_votes(proposal: &RelayRoundProposal, relay_round: Option<&RelayRound>) {
+    println!(
+        "Votes: {} of {} confirmed, {} required",
+        confirmed_votes(proposal),
+        proposal.signers.len(),
+        proposal.required_votes
+    );
+    for (i, vote) in proposal.signers.iter().enumerate() {
+        match relay_round.and_then(|round| round.relays.get(i)) {
+            Some(relay) => println!("  {}: {:?}", relay, vote),
+            None => println!("  #{}: {:?}", i, vote),
+        }
+    }
+}
+
+pub fn print_relay_round(relay_round: &RelayRound) {
+    println!("Round number: {}", relay_round.round_number);
+    println!("Round end: {}", relay_round.round_end);
+    println!("Relays:");
+    for relay in &relay_round.relays {
+        println!("  {}", relay);
+    }
+}
+
+pub fn print_round_loader_settings(settings: &Settings) {
+    println!("Current round number: {}", settings.current_round_number);
+    println!("Round submitter: {}", settings.round_submitter);
+    println!("Min required votes: {}", settings.min_required_votes);
+    println!("Round TTL: {}", settings.round_ttl);
+}
+
+pub fn confirmed_votes(proposal: &RelayRoundProposal) -> u32 {
+    proposal
+        .signers
+        .iter()
+        .filter(|vote| **vote == Vote::Confirm)
+        .count() as u32
+}
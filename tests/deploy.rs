@@ -0,0 +1,465 @@
+use std::str::FromStr;
+
+use solana_program::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_program_test::programs::spl_programs;
+use solana_program_test::ProgramTest;
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+
+use solana_cli::client::Receipt;
+use solana_cli::deploy::{Checkpoint, DeployPlan};
+use solana_cli::error::{Error, OnChainError};
+use solana_cli::journal::{Journal, JournalPlan, Stage};
+use solana_cli::nonblocking::client::*;
+use solana_cli::rpc::{DryRun, Rpc};
+
+/// Any valid SBF program will do, so reuse the SPL Token build that
+/// `solana-program-test` ships with.
+fn program_elf() -> Vec<u8> {
+    let spl_token = Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap();
+    spl_programs(&Rent::default())
+        .into_iter()
+        .find(|(address, _)| *address == spl_token)
+        .map(|(_, account)| account.data().to_vec())
+        .expect("spl token program")
+}
+
+#[tokio::test]
+async fn deploy_program() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+
+    let program_data = program_elf();
+    let max_data_len = program_data.len() * 2;
+
+    let buffer = Keypair::new();
+    let program = Keypair::new();
+    let authority = Pubkey::new_unique();
+
+    create_buffer(
+        &payer,
+        &buffer,
+        &payer.pubkey(),
+        max_data_len,
+        &banks_client,
+    )
+    .await
+    .unwrap();
+    let receipt = write_buffer(
+        &payer,
+        &payer,
+        &buffer.pubkey(),
+        &program_data,
+        &banks_client,
+    )
+    .await
+    .unwrap();
+    assert!(!receipt.signatures.is_empty());
+
+    deploy(
+        &payer,
+        &program,
+        &buffer.pubkey(),
+        &payer,
+        max_data_len,
+        &banks_client,
+    )
+    .await
+    .unwrap();
+    set_program_authority(&payer, &payer, &program.pubkey(), &authority, &banks_client)
+        .await
+        .unwrap();
+
+    let program_account = banks_client
+        .get_account(&program.pubkey())
+        .await
+        .unwrap()
+        .expect("program account");
+    assert!(program_account.executable);
+    assert_eq!(program_account.owner, bpf_loader_upgradeable::id());
+
+    let (programdata_address, _) =
+        Pubkey::find_program_address(&[program.pubkey().as_ref()], &bpf_loader_upgradeable::id());
+    let programdata_account = banks_client
+        .get_account(&programdata_address)
+        .await
+        .unwrap()
+        .expect("programdata account");
+
+    let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+    match bincode::deserialize(&programdata_account.data[..metadata_len]).unwrap() {
+        UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address,
+            ..
+        } => assert_eq!(upgrade_authority_address, Some(authority)),
+        state => panic!("unexpected programdata state: {:?}", state),
+    }
+    assert_eq!(
+        &programdata_account.data[metadata_len..metadata_len + program_data.len()],
+        program_data.as_slice()
+    );
+}
+
+#[tokio::test]
+async fn upload_program_buffer() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+
+    let program_data = program_elf();
+    let buffer = Keypair::new();
+    let authority = Pubkey::new_unique();
+
+    create_buffer(
+        &payer,
+        &buffer,
+        &payer.pubkey(),
+        program_data.len(),
+        &banks_client,
+    )
+    .await
+    .unwrap();
+    write_buffer(
+        &payer,
+        &payer,
+        &buffer.pubkey(),
+        &program_data,
+        &banks_client,
+    )
+    .await
+    .unwrap();
+    set_buffer_authority(&payer, &payer, &buffer.pubkey(), &authority, &banks_client)
+        .await
+        .unwrap();
+
+    let buffer_account = banks_client
+        .get_account(&buffer.pubkey())
+        .await
+        .unwrap()
+        .expect("buffer account");
+
+    let metadata_len = UpgradeableLoaderState::size_of_buffer_metadata();
+    match bincode::deserialize(&buffer_account.data[..metadata_len]).unwrap() {
+        UpgradeableLoaderState::Buffer { authority_address } => {
+            assert_eq!(authority_address, Some(authority))
+        }
+        state => panic!("unexpected buffer state: {:?}", state),
+    }
+    assert_eq!(
+        &buffer_account.data[metadata_len..],
+        program_data.as_slice()
+    );
+}
+
+#[tokio::test]
+async fn deploy_plan() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+
+    let program_data = program_elf();
+    let program = Keypair::new();
+    let authority = Pubkey::new_unique();
+
+    let mut checkpoints = 0;
+    let receipt = DeployPlan::new(&program_data, &payer, &program)
+        .max_data_len(program_data.len() * 2)
+        .final_authority(authority)
+        .deployer(&banks_client)
+        .on_checkpoint(|_| checkpoints += 1)
+        .run()
+        .await
+        .unwrap();
+
+    assert_eq!(checkpoints, 5);
+    assert_eq!(receipt.program_id, program.pubkey());
+    assert_eq!(receipt.upgrade_authority, authority);
+    assert_eq!(receipt.max_data_len, program_data.len() * 2);
+
+    let program_account = banks_client
+        .get_account(&program.pubkey())
+        .await
+        .unwrap()
+        .expect("program account");
+    assert!(program_account.executable);
+    assert!(banks_client
+        .get_account(&receipt.program_data_address)
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[tokio::test]
+async fn deploy_plan_with_separate_signers() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+
+    let program_data = program_elf();
+    let program = Keypair::new();
+    let buffer = Keypair::new();
+    let buffer_authority = Keypair::new();
+    let upgrade_authority = Keypair::new();
+    let authority = Pubkey::new_unique();
+
+    let mut authorities = Vec::new();
+    let receipt = DeployPlan::new(&program_data, &payer, &program)
+        .buffer(&buffer)
+        .buffer_authority(&buffer_authority)
+        .upgrade_authority(&upgrade_authority)
+        .final_authority(authority)
+        .deployer(&banks_client)
+        .on_checkpoint(|checkpoint| match checkpoint {
+            Checkpoint::BufferAuthoritySet { authority, .. }
+            | Checkpoint::AuthoritySet { authority, .. } => authorities.push(authority),
+            _ => {}
+        })
+        .run()
+        .await
+        .unwrap();
+
+    // The payer never holds the buffer or the program
+    assert_eq!(authorities, vec![upgrade_authority.pubkey(), authority]);
+    assert_eq!(receipt.upgrade_authority, authority);
+
+    let program_data = get_program_data(&program.pubkey(), &banks_client)
+        .await
+        .unwrap()
+        .expect("program data");
+    assert_eq!(program_data.upgrade_authority, Some(authority));
+}
+
+#[tokio::test]
+async fn deploy_plan_names_final_authority() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+
+    let program_data = program_elf();
+    let program = Keypair::new();
+    let authority = Keypair::new();
+
+    let mut handoffs = 0;
+    let receipt = DeployPlan::new(&program_data, &payer, &program)
+        .upgrade_authority(&authority)
+        .final_authority(authority.pubkey())
+        .deployer(&banks_client)
+        .on_checkpoint(|checkpoint| {
+            if let Checkpoint::AuthoritySet { .. } = checkpoint {
+                handoffs += 1;
+            }
+        })
+        .run()
+        .await
+        .unwrap();
+
+    // The buffer is handed over before deploying, the program never is
+    assert_eq!(handoffs, 0);
+    assert_eq!(receipt.upgrade_authority, authority.pubkey());
+
+    let program_data = get_program_data(&program.pubkey(), &banks_client)
+        .await
+        .unwrap()
+        .expect("program data");
+    assert_eq!(program_data.upgrade_authority, Some(authority.pubkey()));
+}
+
+#[tokio::test]
+async fn resume_deployment_before_authority_handoff() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+
+    let program_data = program_elf();
+    let buffer = Keypair::new();
+    let program = Keypair::new();
+    let authority = Pubkey::new_unique();
+
+    let path = std::env::temp_dir().join(format!("{}-journal.yml", program.pubkey()));
+    let mut journal = Journal::new(path.clone()).unwrap();
+    journal
+        .start(
+            JournalPlan {
+                program_sha256: hex::encode(hash(&program_data).to_bytes()),
+                payer: payer.pubkey(),
+                program: program.pubkey(),
+                buffer: buffer.pubkey(),
+                buffer_authority: payer.pubkey(),
+                upgrade_authority: payer.pubkey(),
+                max_data_len: program_data.len(),
+                final_authority: Some(authority),
+            },
+            &program,
+            &buffer,
+        )
+        .unwrap();
+
+    // Deployment stops after the program is deployed, before any step is journaled
+    create_buffer(
+        &payer,
+        &buffer,
+        &payer.pubkey(),
+        program_data.len(),
+        &banks_client,
+    )
+    .await
+    .unwrap();
+    write_buffer(
+        &payer,
+        &payer,
+        &buffer.pubkey(),
+        &program_data,
+        &banks_client,
+    )
+    .await
+    .unwrap();
+    deploy(
+        &payer,
+        &program,
+        &buffer.pubkey(),
+        &payer,
+        program_data.len(),
+        &banks_client,
+    )
+    .await
+    .unwrap();
+
+    let journal = Journal::load(path.clone()).unwrap();
+    assert_eq!(journal.completed(), None);
+
+    let receipt = DeployPlan::new(&program_data, &payer, &journal.program_keypair().unwrap())
+        .buffer(&journal.buffer_keypair().unwrap())
+        .max_data_len(program_data.len())
+        .final_authority(authority)
+        .journal(journal)
+        .deployer(&banks_client)
+        .run()
+        .await
+        .unwrap();
+
+    assert_eq!(receipt.cost, None);
+    assert_eq!(receipt.upgrade_authority, authority);
+    assert!(!path.exists());
+
+    let program_data = get_program_data(&program.pubkey(), &banks_client)
+        .await
+        .unwrap()
+        .expect("program data");
+    assert_eq!(program_data.upgrade_authority, Some(authority));
+}
+
+#[tokio::test]
+async fn resume_ignores_journaled_steps_that_never_landed() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+
+    let program_data = program_elf();
+    let buffer = Keypair::new();
+    let program = Keypair::new();
+
+    let path = std::env::temp_dir().join(format!("{}-journal.yml", program.pubkey()));
+    let mut journal = Journal::new(path.clone()).unwrap();
+    journal
+        .start(
+            JournalPlan {
+                program_sha256: hex::encode(hash(&program_data).to_bytes()),
+                payer: payer.pubkey(),
+                program: program.pubkey(),
+                buffer: buffer.pubkey(),
+                buffer_authority: payer.pubkey(),
+                upgrade_authority: payer.pubkey(),
+                max_data_len: program_data.len(),
+                final_authority: None,
+            },
+            &program,
+            &buffer,
+        )
+        .unwrap();
+
+    // Steps recorded by a dry run, none of them landed
+    journal
+        .record(Stage::BufferCreated, &Receipt::default())
+        .unwrap();
+    journal
+        .record(Stage::BufferWritten, &Receipt::default())
+        .unwrap();
+
+    let journal = Journal::load(path.clone()).unwrap();
+    assert_eq!(journal.completed(), Some(Stage::BufferWritten));
+
+    let receipt = DeployPlan::new(&program_data, &payer, &journal.program_keypair().unwrap())
+        .buffer(&journal.buffer_keypair().unwrap())
+        .max_data_len(program_data.len())
+        .journal(journal)
+        .deployer(&banks_client)
+        .run()
+        .await
+        .unwrap();
+
+    assert!(receipt.cost.is_some());
+    assert!(!path.exists());
+
+    let program_data = get_program_data(&program.pubkey(), &banks_client)
+        .await
+        .unwrap()
+        .expect("program data");
+    assert_eq!(program_data.upgrade_authority, Some(payer.pubkey()));
+}
+
+#[tokio::test]
+async fn dry_run_fails_with_the_simulation_error() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+    let connection = DryRun::new(banks_client);
+
+    // Nothing is deployed at this address, so the simulation fails
+    let result = set_program_authority(
+        &payer,
+        &payer,
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        &connection,
+    )
+    .await;
+
+    assert!(matches!(result, Err(Error::OnChain(_))));
+}
+
+#[tokio::test]
+async fn dry_run_skips_steps_depending_on_simulated_accounts() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+    let connection = DryRun::new(banks_client.clone());
+
+    let program_data = program_elf();
+    let program = Keypair::new();
+    let authority = Pubkey::new_unique();
+
+    // Every step after the buffer creation writes to accounts that were
+    // never created, so they are reported as skipped
+    let receipt = DeployPlan::new(&program_data, &payer, &program)
+        .final_authority(authority)
+        .deployer(&connection)
+        .run()
+        .await
+        .unwrap();
+
+    assert!(receipt.receipt.simulated);
+    assert!(receipt.receipt.fee > 0);
+    assert!(banks_client
+        .get_account(&program.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn underfunded_deployment_leaves_no_journal() {
+    let (banks_client, _, _) = ProgramTest::default().start().await;
+
+    let program_data = program_elf();
+    let payer = Keypair::new();
+    let program = Keypair::new();
+
+    let path = std::env::temp_dir().join(format!("{}-journal.yml", program.pubkey()));
+    let result = DeployPlan::new(&program_data, &payer, &program)
+        .journal(Journal::new(path.clone()).unwrap())
+        .deployer(&banks_client)
+        .run()
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::OnChain(OnChainError::InsufficientFunds { .. }))
+    ));
+    assert!(!path.exists());
+}
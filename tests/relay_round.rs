@@ -0,0 +1,299 @@
+use borsh::BorshSerialize;
+
+use solana_banks_client::BanksClient;
+use solana_bridge::bridge_types::Vote;
+use solana_bridge::round_loader::*;
+use solana_program::program_pack::Pack;
+use solana_program_test::ProgramTest;
+use solana_sdk::account::Account;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+
+use solana_cli::error::{Error, OnChainError};
+use solana_cli::nonblocking::client::*;
+use solana_cli::proposal::ProposalInput;
+use solana_cli::rpc::Rpc;
+
+const ROUND_NUMBER: u32 = 0;
+const ROUND_END: u32 = 1_000_000;
+
+fn packed_account<T: Pack>(state: T) -> Account {
+    let mut data = vec![0; T::LEN];
+    T::pack(state, &mut data).unwrap();
+
+    Account {
+        lamports: Rent::default().minimum_balance(T::LEN),
+        data,
+        owner: id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Round loader with its settings and a genesis relay round, where `relay`
+/// is the only relay and is funded to pay for its votes.
+///
+/// The program itself is loaded from `round_loader.so`, so these tests are
+/// ignored by default. Build the bridge contracts and run them with
+///
+/// ```text
+/// BPF_OUT_DIR=<octusbridge-solana-contracts>/target/deploy cargo test --test relay_round -- --ignored
+/// ```
+fn program_test(relay: &Pubkey) -> ProgramTest {
+    let mut program_test = ProgramTest::new("round_loader", id(), None);
+    program_test.add_account(
+        *relay,
+        Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+    );
+
+    let (_, settings_bump) = Pubkey::find_program_address(&[br"settings"], &id());
+    program_test.add_account(
+        get_settings_address(),
+        packed_account(Settings {
+            is_initialized: true,
+            account_kind: AccountKind::Settings(settings_bump),
+            current_round_number: ROUND_NUMBER,
+            round_submitter: *relay,
+            min_required_votes: 1,
+            round_ttl: ROUND_END,
+        }),
+    );
+
+    let (_, relay_round_bump) =
+        Pubkey::find_program_address(&[br"relay_round", &ROUND_NUMBER.to_le_bytes()], &id());
+    program_test.add_account(
+        get_relay_round_address(ROUND_NUMBER),
+        packed_account(RelayRound {
+            is_initialized: true,
+            account_kind: AccountKind::RelayRound(relay_round_bump),
+            round_number: ROUND_NUMBER,
+            round_end: ROUND_END,
+            relays: vec![*relay],
+        }),
+    );
+
+    program_test
+}
+
+fn proposal_input() -> ProposalInput {
+    ProposalInput {
+        event_timestamp: 1,
+        event_transaction_lt: 2,
+        event_configuration: Pubkey::new_unique(),
+        round_number: ROUND_NUMBER + 1,
+        round_end: ROUND_END * 2,
+        relays: (0..MIN_RELAYS).map(|_| Pubkey::new_unique()).collect(),
+    }
+}
+
+/// Creates, writes and finalizes a proposal for the next round.
+async fn finalized_proposal(payer: &Keypair, banks_client: &BanksClient) -> Pubkey {
+    let input = proposal_input();
+    propose_relay_round(payer, ROUND_NUMBER, &input, banks_client)
+        .await
+        .unwrap();
+    input.address(ROUND_NUMBER).unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs round_loader.so in BPF_OUT_DIR"]
+async fn relay_round_proposal() {
+    let relay = Keypair::new();
+    let (banks_client, payer, _) = program_test(&relay.pubkey()).start().await;
+
+    let event_timestamp = 1;
+    let event_transaction_lt = 2;
+    let event_configuration = Pubkey::new_unique();
+
+    let proposal = RelayRoundProposalEventWithLen::new(
+        ROUND_NUMBER + 1,
+        (0..MIN_RELAYS).map(|_| Pubkey::new_unique()).collect(),
+        ROUND_END * 2,
+    );
+    let proposal_pubkey = get_proposal_address(
+        ROUND_NUMBER,
+        event_timestamp,
+        event_transaction_lt,
+        &event_configuration,
+        &proposal.data.try_to_vec().unwrap(),
+    );
+
+    create_relay_round_proposal(
+        &payer,
+        ROUND_NUMBER,
+        event_timestamp,
+        event_transaction_lt,
+        event_configuration,
+        &proposal,
+        &banks_client,
+    )
+    .await
+    .unwrap();
+    write_relay_round_proposal(&payer, &proposal_pubkey, &proposal, &banks_client)
+        .await
+        .unwrap();
+    finalize_relay_round_proposal(&payer, &proposal_pubkey, ROUND_NUMBER, &banks_client)
+        .await
+        .unwrap();
+
+    let proposal_account = banks_client
+        .get_account(&proposal_pubkey)
+        .await
+        .unwrap()
+        .expect("proposal account");
+    assert_eq!(proposal_account.owner, id());
+
+    let state = RelayRoundProposal::unpack_from_slice(&proposal_account.data).unwrap();
+    assert!(state.is_initialized);
+    assert_eq!(state.author, payer.pubkey());
+    assert_eq!(state.round_number, ROUND_NUMBER);
+    assert_eq!(state.event, proposal);
+}
+
+#[tokio::test]
+#[ignore = "needs round_loader.so in BPF_OUT_DIR"]
+async fn resume_interrupted_proposal() {
+    let relay = Keypair::new();
+    let (banks_client, payer, _) = program_test(&relay.pubkey()).start().await;
+
+    let input = proposal_input();
+    let proposal = input.proposal();
+    let proposal_pubkey = input.address(ROUND_NUMBER).unwrap();
+
+    // A run interrupted once the account was created, before any chunk landed
+    create_relay_round_proposal(
+        &payer,
+        ROUND_NUMBER,
+        input.event_timestamp,
+        input.event_transaction_lt,
+        input.event_configuration,
+        &proposal,
+        &banks_client,
+    )
+    .await
+    .unwrap();
+
+    let receipt = propose_relay_round(&payer, ROUND_NUMBER, &input, &banks_client)
+        .await
+        .unwrap();
+    assert!(!receipt.signatures.is_empty());
+
+    // Rerunning a completed proposal sends nothing
+    let receipt = propose_relay_round(&payer, ROUND_NUMBER, &input, &banks_client)
+        .await
+        .unwrap();
+    assert!(receipt.signatures.is_empty());
+
+    let state = get_relay_round_proposal(&proposal_pubkey, &banks_client)
+        .await
+        .unwrap();
+    assert!(state.is_initialized);
+    assert_eq!(state.event, proposal);
+}
+
+#[tokio::test]
+#[ignore = "needs round_loader.so in BPF_OUT_DIR"]
+async fn vote_and_execute_proposal() {
+    let relay = Keypair::new();
+    let (banks_client, payer, _) = program_test(&relay.pubkey()).start().await;
+
+    let proposal_pubkey = finalized_proposal(&payer, &banks_client).await;
+
+    vote_for_relay_round_proposal(&relay, &proposal_pubkey, Vote::Confirm, &banks_client)
+        .await
+        .unwrap();
+    let state = get_relay_round_proposal(&proposal_pubkey, &banks_client)
+        .await
+        .unwrap();
+    assert_eq!(state.signers, vec![Vote::Confirm]);
+
+    execute_relay_round_proposal(&payer, &proposal_pubkey, &banks_client)
+        .await
+        .unwrap();
+    let state = get_relay_round_proposal(&proposal_pubkey, &banks_client)
+        .await
+        .unwrap();
+    assert!(state.is_executed);
+
+    let input = proposal_input();
+    let relay_round = get_relay_round(ROUND_NUMBER + 1, &banks_client)
+        .await
+        .unwrap();
+    assert_eq!(relay_round.round_end, input.round_end);
+    assert_eq!(relay_round.relays.len(), input.relays.len());
+}
+
+#[tokio::test]
+#[ignore = "needs round_loader.so in BPF_OUT_DIR"]
+async fn vote_by_non_relay_is_rejected() {
+    let relay = Keypair::new();
+    let (banks_client, payer, _) = program_test(&relay.pubkey()).start().await;
+
+    let proposal_pubkey = finalized_proposal(&payer, &banks_client).await;
+
+    let outsider = Keypair::new();
+    let result =
+        vote_for_relay_round_proposal(&outsider, &proposal_pubkey, Vote::Confirm, &banks_client)
+            .await;
+    assert!(matches!(
+        result,
+        Err(Error::OnChain(OnChainError::NotARelay(voter, ROUND_NUMBER))) if voter == outsider.pubkey()
+    ));
+}
+
+#[tokio::test]
+#[ignore = "needs round_loader.so in BPF_OUT_DIR"]
+async fn double_vote_is_rejected() {
+    let relay = Keypair::new();
+    let (banks_client, payer, _) = program_test(&relay.pubkey()).start().await;
+
+    let proposal_pubkey = finalized_proposal(&payer, &banks_client).await;
+
+    vote_for_relay_round_proposal(&relay, &proposal_pubkey, Vote::Confirm, &banks_client)
+        .await
+        .unwrap();
+    let result =
+        vote_for_relay_round_proposal(&relay, &proposal_pubkey, Vote::Reject, &banks_client).await;
+    assert!(matches!(result, Err(Error::OnChain(_))));
+
+    let state = get_relay_round_proposal(&proposal_pubkey, &banks_client)
+        .await
+        .unwrap();
+    assert_eq!(state.signers, vec![Vote::Confirm]);
+}
+
+#[tokio::test]
+#[ignore = "needs round_loader.so in BPF_OUT_DIR"]
+async fn execute_below_threshold_is_rejected() {
+    let relay = Keypair::new();
+    let (banks_client, payer, _) = program_test(&relay.pubkey()).start().await;
+
+    let proposal_pubkey = finalized_proposal(&payer, &banks_client).await;
+
+    let result = execute_relay_round_proposal(&payer, &proposal_pubkey, &banks_client).await;
+    assert!(matches!(
+        result,
+        Err(Error::OnChain(OnChainError::NotEnoughVotes {
+            confirmed: 0,
+            required: 1
+        }))
+    ));
+
+    let state = get_relay_round_proposal(&proposal_pubkey, &banks_client)
+        .await
+        .unwrap();
+    assert!(!state.is_executed);
+}
+
+#[tokio::test]
+async fn watch_uses_given_websocket_url() {
+    // Fails connecting, before the round loader is needed
+    let (banks_client, _, _) = ProgramTest::default().start().await;
+
+    let result =
+        watch_relay_round_proposal(&Pubkey::new_unique(), "ws://127.0.0.1:1", &banks_client).await;
+    assert!(matches!(result, Err(Error::Network(_))));
+}
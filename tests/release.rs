@@ -0,0 +1,182 @@
+use std::str::FromStr;
+
+use solana_program_test::programs::spl_programs;
+use solana_program_test::{BanksClient, ProgramTest};
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+
+use solana_cli::nonblocking::client::*;
+use solana_cli::release::{check, LockedProgram, Lockfile, Mismatch, ReleaseStatus};
+
+/// Any valid SBF program will do, so reuse the SPL Token build that
+/// `solana-program-test` ships with.
+fn program_elf() -> Vec<u8> {
+    let spl_token = Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap();
+    spl_programs(&Rent::default())
+        .into_iter()
+        .find(|(address, _)| *address == spl_token)
+        .map(|(_, account)| account.data().to_vec())
+        .expect("spl token program")
+}
+
+/// Uploads `program_data` to a buffer twice its size, owned by the payer.
+async fn upload(payer: &Keypair, program_data: &[u8], banks_client: &BanksClient) -> Pubkey {
+    let buffer = Keypair::new();
+    create_buffer(
+        payer,
+        &buffer,
+        &payer.pubkey(),
+        program_data.len() * 2,
+        banks_client,
+    )
+    .await
+    .unwrap();
+    write_buffer(payer, payer, &buffer.pubkey(), program_data, banks_client)
+        .await
+        .unwrap();
+    buffer.pubkey()
+}
+
+fn locked_program(
+    name: &str,
+    program_id: Pubkey,
+    buffer: Pubkey,
+    program_data: &[u8],
+    authority: Pubkey,
+    status: ReleaseStatus,
+) -> LockedProgram {
+    LockedProgram {
+        name: name.to_string(),
+        program_id,
+        buffer,
+        elf_sha256: hex::encode(hash(program_data).to_bytes()),
+        elf_len: program_data.len(),
+        authority,
+        status,
+        signatures: vec![Signature::new_unique()],
+        slot: 0,
+    }
+}
+
+#[tokio::test]
+async fn check_lockfile() {
+    let (banks_client, payer, _) = ProgramTest::default().start().await;
+
+    let program_data = program_elf();
+    let mut other_data = program_data.clone();
+    other_data[0] ^= 0xff;
+
+    let program = Keypair::new();
+    let buffer = upload(&payer, &program_data, &banks_client).await;
+    deploy(
+        &payer,
+        &program,
+        &buffer,
+        &payer,
+        program_data.len() * 2,
+        &banks_client,
+    )
+    .await
+    .unwrap();
+
+    let pending_buffer = upload(&payer, &program_data, &banks_client).await;
+    let unrelated = Pubkey::new_unique();
+
+    let lockfile = Lockfile {
+        programs: vec![
+            locked_program(
+                "deployed",
+                program.pubkey(),
+                buffer,
+                &program_data,
+                payer.pubkey(),
+                ReleaseStatus::Deployed,
+            ),
+            locked_program(
+                "mismatched_authority",
+                program.pubkey(),
+                buffer,
+                &program_data,
+                unrelated,
+                ReleaseStatus::Upgraded,
+            ),
+            locked_program(
+                "differing_data",
+                program.pubkey(),
+                buffer,
+                &other_data,
+                payer.pubkey(),
+                ReleaseStatus::Upgraded,
+            ),
+            locked_program(
+                "buffered",
+                Pubkey::new_unique(),
+                pending_buffer,
+                &program_data,
+                payer.pubkey(),
+                ReleaseStatus::Buffered,
+            ),
+            locked_program(
+                "buffered_differing_data",
+                Pubkey::new_unique(),
+                pending_buffer,
+                &other_data,
+                payer.pubkey(),
+                ReleaseStatus::Buffered,
+            ),
+            locked_program(
+                "buffered_gone",
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                &program_data,
+                payer.pubkey(),
+                ReleaseStatus::Buffered,
+            ),
+            locked_program(
+                "buffered_upgraded",
+                program.pubkey(),
+                Pubkey::new_unique(),
+                &program_data,
+                payer.pubkey(),
+                ReleaseStatus::Buffered,
+            ),
+        ],
+    };
+
+    let mismatches = check(&lockfile, &banks_client).await.unwrap();
+    let mismatch = |name: &str, reason: String| Mismatch {
+        name: name.to_string(),
+        reason,
+    };
+    assert_eq!(
+        mismatches,
+        vec![
+            mismatch(
+                "mismatched_authority",
+                format!(
+                    "upgrade authority is ({}), expected ({})",
+                    payer.pubkey(),
+                    unrelated
+                ),
+            ),
+            mismatch(
+                "differing_data",
+                "program data differs from the locked ELF".to_string(),
+            ),
+            mismatch(
+                "buffered_differing_data",
+                format!("buffer ({}) content differs", pending_buffer),
+            ),
+            mismatch(
+                "buffered_gone",
+                format!(
+                    "program is not upgraded and buffer ({}) is gone",
+                    lockfile.programs[5].buffer
+                ),
+            ),
+        ]
+    );
+}